@@ -0,0 +1,135 @@
+use crate::db::CommandLog;
+use crate::shell::{self, Shell};
+use crate::workflows;
+use chrono::Duration;
+use std::collections::{HashMap, HashSet};
+
+pub enum Kind {
+    /// The whole command, typed the same way every time
+    Command,
+    /// A common leading part of commands that differ in their last arguments
+    Prefix,
+    /// Commands that are run one after the other
+    Sequence(Vec<String>),
+}
+
+pub struct AliasSuggestion {
+    pub name: String,
+    /// What the alias expands to; for sequences, the commands joined with `&&`
+    pub expansion: String,
+    pub kind: Kind,
+    pub uses: usize,
+    pub keystrokes_saved: usize,
+}
+
+/// Suggests aliases for long commands and command prefixes used at least
+/// `min_count` times, and shell functions for commands run back to back (each
+/// within `sequence_gap` of the previous one) at least that often. Only
+/// successful runs count. Best savings first.
+pub fn suggest(logs: &[&CommandLog], min_count: usize, min_length: usize, sequence_gap: Duration) -> Vec<AliasSuggestion> {
+    let ok: Vec<&CommandLog> = logs.iter().copied().filter(|log| log.exit_code == 0).collect();
+    let mut commands: HashMap<&str, usize> = HashMap::new();
+    let mut prefixes: HashMap<String, usize> = HashMap::new();
+    for log in &ok {
+        let command = log.command.trim();
+        *commands.entry(command).or_default() += 1;
+        let words: Vec<&str> = command.split_whitespace().collect();
+        for n in 2..words.len() {
+            *prefixes.entry(words[..n].join(" ")).or_default() += 1;
+        }
+    }
+
+    let mut candidates: Vec<(String, Kind, usize)> = Vec::new();
+    for (command, uses) in &commands {
+        if *uses >= min_count && command.len() >= min_length {
+            candidates.push((command.to_string(), Kind::Command, *uses));
+        }
+    }
+    for (prefix, uses) in &prefixes {
+        // A prefix is only worth it if it is not just one command repeated
+        if *uses >= min_count && prefix.len() >= min_length && commands.get(prefix.as_str()).copied().unwrap_or(0) < *uses / 2 {
+            candidates.push((prefix.clone(), Kind::Prefix, *uses));
+        }
+    }
+    // A shorter prefix is redundant when a longer one is used just as often
+    let covered: Vec<bool> = candidates
+        .iter()
+        .map(|(expansion, kind, uses)| {
+            matches!(kind, Kind::Prefix)
+                && prefixes.iter().any(|(other, other_uses)| other_uses >= uses && other.starts_with(&format!("{} ", expansion)))
+        })
+        .collect();
+    let mut covered = covered.into_iter();
+    candidates.retain(|_| !covered.next().unwrap());
+    for workflow in workflows::mine(&ok, sequence_gap, 3, min_count) {
+        candidates.push((workflow.commands.join(" && "), Kind::Sequence(workflow.commands), workflow.count));
+    }
+
+    // Sort by keystrokes typed today so the biggest wins get the shortest names
+    let mut suggestions: Vec<AliasSuggestion> = candidates
+        .into_iter()
+        .map(|(expansion, kind, uses)| {
+            let typed = match &kind {
+                Kind::Sequence(commands) => commands.iter().map(|c| c.len() + 1).sum(),
+                _ => expansion.len(),
+            };
+            AliasSuggestion { name: String::new(), keystrokes_saved: uses * typed, expansion, kind, uses }
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.keystrokes_saved.cmp(&a.keystrokes_saved).then_with(|| a.expansion.cmp(&b.expansion)));
+    let mut taken: HashSet<String> = HashSet::new();
+    for suggestion in &mut suggestions {
+        suggestion.name = unique_name(&suggestion.expansion, &mut taken);
+        suggestion.keystrokes_saved = suggestion.keystrokes_saved.saturating_sub(suggestion.uses * suggestion.name.len());
+    }
+    suggestions.retain(|s| s.keystrokes_saved > 0);
+    suggestions.sort_by(|a, b| b.keystrokes_saved.cmp(&a.keystrokes_saved).then_with(|| a.expansion.cmp(&b.expansion)));
+    suggestions
+}
+
+/// Initials of the words in `expansion` (`git push origin main` → `gpom`),
+/// made unique against `taken` and programs on `$PATH`.
+fn unique_name(expansion: &str, taken: &mut HashSet<String>) -> String {
+    let mut base: String = expansion
+        .split_whitespace()
+        .filter(|w| *w != "&&")
+        .filter_map(|w| w.chars().find(|c| c.is_ascii_alphanumeric()))
+        .map(|c| c.to_ascii_lowercase())
+        .take(5)
+        .collect();
+    if base.len() < 2 {
+        base = expansion.chars().filter(|c| c.is_ascii_alphanumeric()).take(3).collect::<String>().to_ascii_lowercase();
+    }
+    let mut name = base.clone();
+    let mut n = 2;
+    while taken.contains(&name) || on_path(&name) {
+        name = format!("{}{}", base, n);
+        n += 1;
+    }
+    taken.insert(name.clone());
+    name
+}
+
+fn on_path(name: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(name).exists()))
+}
+
+/// Definitions for `suggestions` in the given shell's syntax.
+pub fn render(suggestions: &[AliasSuggestion], shell: Shell) -> String {
+    let mut out = String::new();
+    for suggestion in suggestions {
+        match (&suggestion.kind, shell) {
+            (Kind::Sequence(commands), Shell::Fish) => {
+                out.push_str(&format!("function {}\n    {}\nend\n", suggestion.name, commands.join(" && ")));
+            }
+            (Kind::Sequence(commands), _) => {
+                out.push_str(&format!("{}() {{\n    {}\n}}\n", suggestion.name, commands.join(" && ")));
+            }
+            _ => {
+                out.push_str(&shell::alias(&suggestion.name, &suggestion.expansion, shell));
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,377 @@
+use crate::crypto::{self, Keyring};
+use crate::db::format_ts;
+use chrono::DateTime;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+
+const FORMAT: &str = "prynt-dump";
+/// Bumped whenever a record changes in a way older versions cannot read.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// One line of a dump. The header comes first, and commands come before the
+/// tags and notes that refer to them.
+#[derive(Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Header {
+        format: String,
+        version: u32,
+        created_at: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+    },
+    Command {
+        id: String,
+        started_at: String,
+        ended_at: String,
+        cwd: String,
+        command: String,
+        exit_code: i32,
+        duration_ns: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rerun_of: Option<String>,
+    },
+    Tag {
+        log_id: String,
+        tag: String,
+    },
+    Note {
+        log_id: String,
+        note: String,
+    },
+    Snippet {
+        name: String,
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source_id: Option<String>,
+        created_at: String,
+    },
+    Rollup {
+        day: String,
+        cwd: String,
+        command: String,
+        runs: i64,
+        failures: i64,
+        duration_ns: i64,
+        min_duration_ns: i64,
+        max_duration_ns: i64,
+    },
+    /// A command deleted with `prynt forget`, to be deleted everywhere
+    Forget {
+        id: String,
+    },
+}
+
+/// An archived row that exists locally with different contents. The local
+/// version is kept.
+pub struct Conflict {
+    pub kind: &'static str,
+    pub key: String,
+    pub detail: String,
+}
+
+#[derive(Default)]
+pub struct MergeReport {
+    pub commands: usize,
+    pub tags: usize,
+    pub notes: usize,
+    pub snippets: usize,
+    pub rollups: usize,
+    pub forgotten: usize,
+    /// Rows that were already present unchanged
+    pub unchanged: usize,
+    pub conflicts: Vec<Conflict>,
+    /// Records that were skipped because they are malformed
+    pub errors: Vec<String>,
+}
+
+/// The first record of a dump.
+pub fn header(format: &str) -> Record {
+    Record::Header {
+        format: format.to_string(),
+        version: FORMAT_VERSION,
+        created_at: crate::db::format_ts(&crate::clock::now()),
+        host: gethostname::gethostname().into_string().ok(),
+    }
+}
+
+/// Every record in the database, header first.
+pub fn records(conn: &Connection) -> rusqlite::Result<Vec<Record>> {
+    let mut records = vec![header(FORMAT)];
+    let mut stmt = conn.prepare(
+        "SELECT id, started_at, ended_at, cwd, command, exit_code, duration_ns, rerun_of FROM command_logs ORDER BY started_at",
+    )?;
+    for record in stmt.query_map([], |row| {
+        Ok(Record::Command {
+            id: row.get(0)?,
+            started_at: row.get(1)?,
+            ended_at: row.get(2)?,
+            cwd: row.get(3)?,
+            command: row.get(4)?,
+            exit_code: row.get(5)?,
+            duration_ns: row.get(6)?,
+            rerun_of: row.get(7)?,
+        })
+    })? {
+        records.push(record?);
+    }
+    let mut stmt = conn.prepare("SELECT log_id, tag FROM command_tags ORDER BY log_id, tag")?;
+    for record in stmt.query_map([], |row| Ok(Record::Tag { log_id: row.get(0)?, tag: row.get(1)? }))? {
+        records.push(record?);
+    }
+    let mut stmt = conn.prepare("SELECT log_id, note FROM command_notes ORDER BY log_id")?;
+    for record in stmt.query_map([], |row| Ok(Record::Note { log_id: row.get(0)?, note: row.get(1)? }))? {
+        records.push(record?);
+    }
+    let mut stmt = conn.prepare("SELECT name, command, description, source_id, created_at FROM snippets ORDER BY name")?;
+    for record in stmt.query_map([], |row| {
+        Ok(Record::Snippet {
+            name: row.get(0)?,
+            command: row.get(1)?,
+            description: row.get(2)?,
+            source_id: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })? {
+        records.push(record?);
+    }
+    let mut stmt = conn.prepare(
+        "SELECT day, cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns FROM daily_rollups ORDER BY day, cwd, command",
+    )?;
+    for record in stmt.query_map([], |row| {
+        Ok(Record::Rollup {
+            day: row.get(0)?,
+            cwd: row.get(1)?,
+            command: row.get(2)?,
+            runs: row.get(3)?,
+            failures: row.get(4)?,
+            duration_ns: row.get(5)?,
+            min_duration_ns: row.get(6)?,
+            max_duration_ns: row.get(7)?,
+        })
+    })? {
+        records.push(record?);
+    }
+    let mut stmt = conn.prepare("SELECT id FROM forgotten ORDER BY id")?;
+    for record in stmt.query_map([], |row| Ok(Record::Forget { id: row.get(0)? }))? {
+        records.push(record?);
+    }
+    Ok(records)
+}
+
+/// Writes `records` as gzip-compressed NDJSON, encrypted with the current key
+/// of `keyring` if there is one.
+pub fn write(records: &[Record], mut out: impl Write, keyring: Option<&Keyring>) -> Result<(), Box<dyn Error>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for record in records {
+        serde_json::to_writer(&mut encoder, record)?;
+        encoder.write_all(b"\n")?;
+    }
+    let compressed = encoder.finish()?;
+    match keyring {
+        Some(keyring) => out.write_all(keyring.encrypt(&compressed).as_bytes())?,
+        None => out.write_all(&compressed)?,
+    }
+    Ok(())
+}
+
+/// Reads a dump written by `write`, checking its header.
+pub fn read(mut input: impl Read, keyring: Option<&Keyring>) -> Result<Vec<Record>, Box<dyn Error>> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    if crypto::is_encrypted(&data) {
+        let Some(keyring) = keyring else {
+            return Err("archive is encrypted; set up the key with `prynt key init --keyfile` or `--passphrase`".into());
+        };
+        data = keyring.decrypt(&String::from_utf8_lossy(&data))?;
+    }
+    let mut records = Vec::new();
+    for line in BufReader::new(GzDecoder::new(data.as_slice())).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(&line)?;
+        if records.is_empty() {
+            match &record {
+                Record::Header { format, version, .. } if format == FORMAT => {
+                    if *version > FORMAT_VERSION {
+                        return Err(format!("archive is format version {}, this prynt reads up to {}; upgrade prynt", version, FORMAT_VERSION).into());
+                    }
+                }
+                _ => return Err("not a prynt dump (missing header)".into()),
+            }
+        }
+        records.push(record);
+    }
+    if records.is_empty() {
+        return Err("archive is empty".into());
+    }
+    Ok(records)
+}
+
+/// Merges `records` into the database in one transaction; see `apply`.
+pub fn merge(conn: &Connection, records: &[Record]) -> rusqlite::Result<MergeReport> {
+    let tx = conn.unchecked_transaction()?;
+    let report = apply(&tx, records)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Merges `records` into the database, keyed by command id, snippet name and
+/// rollup day/directory/command. Rows missing locally are added, tags are
+/// combined, and rows that differ are left alone and reported as conflicts.
+/// Forgotten commands are deleted and never added back.
+pub fn apply(tx: &Connection, records: &[Record]) -> rusqlite::Result<MergeReport> {
+    let mut report = MergeReport::default();
+    for record in records {
+        match record {
+            Record::Header { .. } => {}
+            Record::Command { id, started_at, ended_at, cwd, command, exit_code, duration_ns, rerun_of } => {
+                // Stored the way `format_ts` writes them, so they parse and sort like local rows
+                let (Ok(started), Ok(ended)) = (DateTime::parse_from_rfc3339(started_at), DateTime::parse_from_rfc3339(ended_at)) else {
+                    report.errors.push(format!("command {} has an invalid start or end time and was skipped", id));
+                    continue;
+                };
+                let (started_at, ended_at) = (&format_ts(&started), &format_ts(&ended));
+                let local = tx
+                    .query_row(
+                        "SELECT started_at, ended_at, cwd, command, exit_code, duration_ns FROM command_logs WHERE id = ?1",
+                        [id],
+                        |row| {
+                            Ok((
+                                row.get::<_, String>(0)?,
+                                row.get::<_, String>(1)?,
+                                row.get::<_, String>(2)?,
+                                row.get::<_, String>(3)?,
+                                row.get::<_, i32>(4)?,
+                                row.get::<_, i64>(5)?,
+                            ))
+                        },
+                    )
+                    .optional()?;
+                let forgotten: bool = tx.query_row("SELECT EXISTS (SELECT 1 FROM forgotten WHERE id = ?1)", [id], |row| row.get(0))?;
+                match local {
+                    None if forgotten => report.unchanged += 1,
+                    None => {
+                        tx.execute(
+                            "INSERT INTO command_logs (id, timestamp, cwd, command, exit_code, duration_secs, started_at, ended_at, duration_ns, rerun_of)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                            params![id, ended_at, cwd, command, exit_code, *duration_ns as f64 / 1e9, started_at, ended_at, duration_ns, rerun_of],
+                        )?;
+                        report.commands += 1;
+                    }
+                    Some(local) => {
+                        let mut differs = Vec::new();
+                        for (field, same) in [
+                            ("started_at", local.0 == *started_at),
+                            ("ended_at", local.1 == *ended_at),
+                            ("cwd", local.2 == *cwd),
+                            ("command", local.3 == *command),
+                            ("exit_code", local.4 == *exit_code),
+                            ("duration_ns", local.5 == *duration_ns),
+                        ] {
+                            if !same {
+                                differs.push(field);
+                            }
+                        }
+                        if differs.is_empty() {
+                            report.unchanged += 1;
+                        } else {
+                            report.conflicts.push(Conflict { kind: "command", key: id.clone(), detail: format!("differs in {}", differs.join(", ")) });
+                        }
+                    }
+                }
+            }
+            Record::Tag { log_id, tag } => {
+                let added = tx.execute(
+                    "INSERT OR IGNORE INTO command_tags (log_id, tag) SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM command_logs WHERE id = ?1)",
+                    params![log_id, tag],
+                )?;
+                if added > 0 {
+                    report.tags += 1;
+                } else {
+                    report.unchanged += 1;
+                }
+            }
+            Record::Note { log_id, note } => {
+                let local: Option<String> = tx.query_row("SELECT note FROM command_notes WHERE log_id = ?1", [log_id], |row| row.get(0)).optional()?;
+                match local {
+                    None => {
+                        let added = tx.execute(
+                            "INSERT INTO command_notes (log_id, note) SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM command_logs WHERE id = ?1)",
+                            params![log_id, note],
+                        )?;
+                        if added > 0 {
+                            report.notes += 1;
+                        } else {
+                            report.unchanged += 1;
+                        }
+                    }
+                    Some(local) if local == *note => report.unchanged += 1,
+                    Some(local) => report.conflicts.push(Conflict {
+                        kind: "note",
+                        key: log_id.clone(),
+                        detail: format!("local '{}', archive '{}'", local, note),
+                    }),
+                }
+            }
+            Record::Snippet { name, command, description, source_id, created_at } => {
+                let local: Option<String> = tx.query_row("SELECT command FROM snippets WHERE name = ?1", [name], |row| row.get(0)).optional()?;
+                match local {
+                    None => {
+                        tx.execute(
+                            "INSERT INTO snippets (name, command, description, source_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![name, command, description, source_id, created_at],
+                        )?;
+                        report.snippets += 1;
+                    }
+                    Some(local) if local == *command => report.unchanged += 1,
+                    Some(local) => report.conflicts.push(Conflict {
+                        kind: "snippet",
+                        key: name.clone(),
+                        detail: format!("local '{}', archive '{}'", local, command),
+                    }),
+                }
+            }
+            Record::Rollup { day, cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns } => {
+                let local: Option<(i64, i64)> = tx
+                    .query_row(
+                        "SELECT runs, duration_ns FROM daily_rollups WHERE day = ?1 AND cwd = ?2 AND command = ?3",
+                        params![day, cwd, command],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?;
+                match local {
+                    None => {
+                        tx.execute(
+                            "INSERT INTO daily_rollups (day, cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                            params![day, cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns],
+                        )?;
+                        report.rollups += 1;
+                    }
+                    Some(local) if local == (*runs, *duration_ns) => report.unchanged += 1,
+                    Some(local) => report.conflicts.push(Conflict {
+                        kind: "rollup",
+                        key: format!("{} {} {}", day, cwd, command),
+                        detail: format!("local {} runs, archive {} runs", local.0, runs),
+                    }),
+                }
+            }
+            Record::Forget { id } => {
+                let deleted = tx.execute("DELETE FROM command_logs WHERE id = ?1", [id])?;
+                tx.execute("INSERT OR IGNORE INTO forgotten (id) VALUES (?1)", [id])?;
+                report.forgotten += deleted;
+            }
+        }
+    }
+    Ok(report)
+}
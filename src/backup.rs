@@ -0,0 +1,104 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use crate::retention::{self, Rollup};
+use crate::storage;
+use rusqlite::backup;
+use rusqlite::{Connection, Result};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+/// A snapshot of the history database, named
+/// `prynt-<time>-<reason>.sqlite` so the newest sorts last.
+pub struct Backup {
+    pub path: PathBuf,
+    pub created_at: DateTime<Local>,
+    /// Why the backup was taken: `manual`, or the operation it preceded, e.g. `clear`
+    pub reason: String,
+    pub size: u64,
+}
+
+/// Copies the open database into the existing directory `dir` with SQLite's
+/// online backup API, which gives a consistent snapshot even while shell hooks
+/// keep writing. An encrypted database is backed up encrypted with its `key`.
+pub fn create(conn: &Connection, dir: &Path, reason: &str, key: Option<&str>) -> Result<PathBuf, Box<dyn Error>> {
+    let path = dir.join(format!("prynt-{}-{}.sqlite", crate::clock::now().format(TIME_FORMAT), reason));
+    // SQLite keeps the permissions of an existing file
+    storage::write_private(&path, b"")?;
+    let mut dest = storage::open(&path, key)?;
+    backup::Backup::new(conn, &mut dest)?.run_to_completion(100, std::time::Duration::ZERO, None)?;
+    Ok(path)
+}
+
+/// Every backup in `dir`, newest first.
+pub fn list(dir: &Path) -> std::io::Result<Vec<Backup>> {
+    let mut backups = Vec::new();
+    if !dir.is_dir() {
+        return Ok(backups);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(stem) = name.strip_prefix("prynt-").and_then(|n| n.strip_suffix(".sqlite")) else { continue };
+        // The time is a fixed 19 characters, followed by `-<reason>`
+        let Some((time, reason)) = stem.split_at_checked(19) else { continue };
+        let Ok(time) = NaiveDateTime::parse_from_str(time, TIME_FORMAT) else { continue };
+        let Some(created_at) = Local.from_local_datetime(&time).earliest() else { continue };
+        backups.push(Backup {
+            path: entry.path(),
+            created_at,
+            reason: reason.trim_start_matches('-').to_string(),
+            size: entry.metadata()?.len(),
+        });
+    }
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    Ok(backups)
+}
+
+/// Deletes all but the newest `keep` backups. Returns how many were removed.
+pub fn rotate(dir: &Path, keep: usize) -> std::io::Result<usize> {
+    let backups = list(dir)?;
+    let mut removed = 0;
+    for backup in backups.iter().skip(keep) {
+        std::fs::remove_file(&backup.path)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Replaces the contents of the open database with the backup at `path`,
+/// which is encrypted with the same `key` as the database.
+pub fn restore(conn: &mut Connection, path: &Path, key: Option<&str>) -> Result<()> {
+    let source = storage::open(path, key)?;
+    backup::Backup::new(&source, conn)?.run_to_completion(100, std::time::Duration::ZERO, None)
+}
+
+/// Deletes the commands with `ids` or whose text `redact` matches (with their
+/// tags and notes) from every backup in `dir` and redacts the daily totals
+/// `redact` matches, as `prynt forget` does to the database. Returns each
+/// backup that held any of them, or could not be changed, with the outcome.
+pub fn forget(dir: &Path, ids: &[String], redact: &dyn Fn(&str) -> bool, key: Option<&str>) -> std::io::Result<Vec<(Backup, Result<()>)>> {
+    let mut touched = Vec::new();
+    for backup in list(dir)? {
+        match forget_in(&backup.path, ids, redact, key) {
+            Ok(false) => {}
+            Ok(true) => touched.push((backup, Ok(()))),
+            Err(e) => touched.push((backup, Err(e))),
+        }
+    }
+    Ok(touched)
+}
+
+fn forget_in(path: &Path, ids: &[String], redact: &dyn Fn(&str) -> bool, key: Option<&str>) -> Result<bool> {
+    let conn = storage::open(path, key)?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    let mut stmt = conn.prepare("SELECT id, command FROM command_logs")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?.collect::<Result<Vec<_>>>()?;
+    drop(stmt);
+    let matching: Vec<String> = rows.into_iter().filter(|(id, command)| ids.contains(id) || redact(command)).map(|(id, _)| id).collect();
+    let changed = retention::prune(&conn, &matching, Rollup::None)? + retention::redact_rollups(&conn, redact)?;
+    if changed > 0 {
+        retention::compact(&conn)?;
+    }
+    Ok(changed > 0)
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, Local};
+use std::cell::Cell;
+
+thread_local! {
+    static FROZEN: Cell<Option<DateTime<Local>>> = const { Cell::new(None) };
+}
+
+/// The current time, as every part of prynt reads it. Tests pin it with
+/// `freeze`; `PRYNT_NOW` (an RFC 3339 timestamp) pins it for a whole process,
+/// which makes reports reproducible.
+pub fn now() -> DateTime<Local> {
+    if let Some(time) = FROZEN.get() {
+        return time;
+    }
+    match std::env::var("PRYNT_NOW") {
+        Ok(time) => DateTime::parse_from_rfc3339(&time).expect("PRYNT_NOW must be an RFC 3339 timestamp").with_timezone(&Local),
+        Err(_) => Local::now(),
+    }
+}
+
+/// Makes `now` return `time` on this thread until the guard is dropped.
+pub fn freeze(time: DateTime<Local>) -> Frozen {
+    FROZEN.set(Some(time));
+    Frozen(())
+}
+
+#[must_use = "the clock is only frozen while the guard is alive"]
+pub struct Frozen(());
+
+impl Drop for Frozen {
+    fn drop(&mut self) {
+        FROZEN.set(None);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use std::path::Path;
+
+/// User settings read from `~/.context/config.toml`. Every field has a
+/// default, so the file and any of its sections are optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub sessions: SessionConfig,
+    pub timesheet: TimesheetConfig,
+    pub retention: RetentionConfig,
+    pub backup: BackupConfig,
+    pub sync: SyncConfig,
+    pub encryption: EncryptionConfig,
+    pub database: DatabaseConfig,
+    pub team: TeamConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// A pause longer than this between two commands ends a work session
+    pub idle_gap_minutes: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig { idle_gap_minutes: 15 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TimesheetConfig {
+    /// Active time per row is rounded up to a multiple of this (0 disables rounding)
+    pub round_minutes: u64,
+}
+
+impl Default for TimesheetConfig {
+    fn default() -> Self {
+        TimesheetConfig { round_minutes: 15 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Raw command rows older than this many days are pruned automatically (never when unset)
+    pub keep_raw_days: Option<u64>,
+    /// Fold pruned rows into daily per-command totals so all-time reports keep working
+    pub keep_aggregates: bool,
+    /// Never prune commands that have tags or a note
+    pub keep_tagged: bool,
+    /// How often retention and VACUUM/ANALYZE run
+    pub maintenance_interval_days: u64,
+}
+
+impl RetentionConfig {
+    /// Checks that the day counts reach no further back than dates can go.
+    fn validate(&self) -> Result<(), String> {
+        for (name, days) in [("keep_raw_days", self.keep_raw_days), ("maintenance_interval_days", Some(self.maintenance_interval_days))] {
+            if let Some(days) = days
+                && crate::retention::days_ago(days).is_none()
+            {
+                return Err(format!("retention.{} = {} is too long", name, days));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig { keep_raw_days: None, keep_aggregates: true, keep_tagged: true, maintenance_interval_days: 7 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// How many automatic and manual backups to keep; older ones are deleted
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig { keep: 10 }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// Shared folder or `prynt serve` URL that `prynt sync` uses by default
+    pub target: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Key file for dumps and sync (defaults to ~/.context/prynt.key)
+    pub keyfile: Option<std::path::PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// Where history is kept
+    pub backend: Backend,
+    /// Key file for an encrypted database (defaults to ~/.context/prynt-db.key)
+    pub keyfile: Option<std::path::PathBuf>,
+    /// History file of the `jsonl` backend (defaults to ~/.context/prynt.jsonl)
+    pub history_file: Option<std::path::PathBuf>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// The SQLite database, with tags, notes, snippets, sync and backups
+    #[default]
+    Sqlite,
+    /// An append-only JSON Lines file holding only the commands
+    Jsonl,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TeamConfig {
+    /// `prynt serve --team` URL that `prynt share` uses by default
+    pub url: Option<String>,
+}
+
+pub fn load_config(path: &Path) -> Config {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Config::default();
+    };
+    match toml::from_str::<Config>(&contents).map_err(|e| e.to_string()).and_then(|config| config.retention.validate().map(|()| config)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Warning: ignoring invalid config {}: {}", path.display(), e);
+            Config::default()
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Marks encrypted payloads: `prynt-enc1:<key id>:<base64 of nonce and ciphertext>`.
+const PREFIX: &str = "prynt-enc1";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+pub struct Key {
+    /// Short fingerprint stored with every payload, so the right key can be
+    /// picked after a rotation
+    pub id: String,
+    /// `random` or `passphrase`
+    pub source: String,
+    pub created_at: String,
+    /// Base64 salt a passphrase key was derived with; other machines need it,
+    /// with the passphrase, to derive the same key. Key files from before
+    /// salts were random have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    key: String,
+}
+
+impl Key {
+    fn new(bytes: &[u8], source: &str) -> Key {
+        Key {
+            id: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, bytes).simple().to_string()[..8].to_string(),
+            source: source.to_string(),
+            created_at: crate::db::format_ts(&crate::clock::now()),
+            salt: None,
+            key: BASE64.encode(bytes),
+        }
+    }
+
+    fn cipher(&self) -> Result<XChaCha20Poly1305, String> {
+        let bytes = BASE64.decode(&self.key).map_err(|e| format!("key {} is corrupt: {}", self.id, e))?;
+        XChaCha20Poly1305::new_from_slice(&bytes).map_err(|_| format!("key {} has the wrong length", self.id))
+    }
+}
+
+pub fn random_key() -> Key {
+    Key::new(&XChaCha20Poly1305::generate_key(&mut OsRng), "random")
+}
+
+/// Derives a key from `passphrase` with Argon2id and `salt` (base64), or a
+/// new random salt for the first machine.
+pub fn passphrase_key(passphrase: &str, salt: Option<&str>) -> Result<Key, String> {
+    let salt = match salt {
+        Some(salt) => BASE64.decode(salt.trim()).map_err(|e| format!("salt '{}' is not valid base64: {}", salt, e))?,
+        None => {
+            let mut salt = vec![0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        }
+    };
+    let mut bytes = [0u8; 32];
+    argon2::Argon2::default().hash_password_into(passphrase.as_bytes(), &salt, &mut bytes).map_err(|e| format!("could not derive a key with that salt: {}", e))?;
+    let mut key = Key::new(&bytes, "passphrase");
+    key.salt = Some(BASE64.encode(&salt));
+    Ok(key)
+}
+
+/// The keys in a key file. The first encrypts; all of them decrypt, so data
+/// written before a rotation stays readable.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Keyring {
+    pub keys: Vec<Key>,
+}
+
+impl Keyring {
+    /// Reads the key file at `path`, or `None` if there is none.
+    pub fn load(path: &Path) -> Result<Option<Keyring>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map(Some).map_err(|e| format!("{} is not a prynt key file: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Writes the key file, readable only by the current user.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        crate::storage::write_private(path, serde_json::to_string_pretty(self).unwrap().as_bytes())
+    }
+
+    /// Makes `key` the one used for encryption, keeping the others for reading.
+    pub fn rotate(&mut self, key: Key) {
+        self.keys.retain(|k| k.id != key.id);
+        self.keys.insert(0, key);
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> String {
+        let key = &self.keys[0];
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(key.cipher().unwrap().encrypt(&nonce, plaintext).expect("Failed to encrypt"));
+        format!("{}:{}:{}", PREFIX, key.id, BASE64.encode(sealed))
+    }
+
+    pub fn decrypt(&self, payload: &str) -> Result<Vec<u8>, String> {
+        let mut parts = payload.trim().splitn(3, ':');
+        let (Some(PREFIX), Some(id), Some(data)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err("not an encrypted prynt payload".to_string());
+        };
+        let Some(key) = self.keys.iter().find(|k| k.id == id) else {
+            return Err(format!("encrypted with key {}, which is not in your key file", id));
+        };
+        let sealed = BASE64.decode(data).map_err(|e| e.to_string())?;
+        if sealed.len() < NONCE_LEN {
+            return Err("encrypted payload is truncated".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        key.cipher()?.decrypt(XNonce::from_slice(nonce), ciphertext).map_err(|_| format!("payload does not decrypt with key {}; it was altered", id))
+    }
+}
+
+pub fn is_encrypted(payload: &[u8]) -> bool {
+    payload.starts_with(PREFIX.as_bytes())
+}
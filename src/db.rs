@@ -1,17 +1,71 @@
-use rusqlite::{params, Connection, Result};
-use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use chrono::{DateTime, Duration, Local, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CommandLog {
     pub id: String,
-    pub timestamp: DateTime<Local>,
+    pub started_at: DateTime<Local>,
+    pub ended_at: DateTime<Local>,
     pub cwd: String,
     pub command: String,
     pub exit_code: i32,
-    pub duration_secs: f64,
+    pub duration_ns: i64,
 }
 
-pub fn init_db(db_path: &str) -> Result<Connection> {
-    let conn = Connection::open(db_path)?;
+impl CommandLog {
+    pub fn duration_secs(&self) -> f64 {
+        self.duration_ns as f64 / 1_000_000_000.0
+    }
+
+    /// Whether this is a `prynt` invocation, which reports leave out.
+    pub fn is_prynt(&self) -> bool {
+        let trimmed = self.command.trim_start();
+        trimmed == "prynt" || trimmed.starts_with("prynt ")
+    }
+}
+
+/// Columns selected by every query that builds a `CommandLog` with `log_from_row`.
+pub const LOG_COLUMNS: &str = "id, started_at, ended_at, cwd, command, exit_code, duration_ns";
+
+/// Tags, note and rerun link attached to a logged command.
+#[derive(Default)]
+pub struct Annotations {
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    /// Id of the command `prynt rerun` ran again to produce this one
+    pub rerun_of: Option<String>,
+}
+
+/// Formats a timestamp for storage. Always UTC with a fixed number of
+/// fractional digits so that string comparison in SQL matches time order.
+pub fn format_ts<Tz: chrono::TimeZone>(ts: &DateTime<Tz>) -> String {
+    ts.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+pub fn parse_ts(ts: &str) -> DateTime<Local> {
+    DateTime::parse_from_rfc3339(ts).unwrap().with_timezone(&Local)
+}
+
+pub fn log_from_row(row: &Row) -> Result<CommandLog> {
+    Ok(CommandLog {
+        id: row.get(0)?,
+        started_at: parse_ts(&row.get::<_, String>(1)?),
+        ended_at: parse_ts(&row.get::<_, String>(2)?),
+        cwd: row.get(3)?,
+        command: row.get(4)?,
+        exit_code: row.get(5)?,
+        duration_ns: row.get(6)?,
+    })
+}
+
+/// Opens the history database, unlocking it with `key` if it is encrypted,
+/// and creates or migrates its tables.
+pub fn init_db(db_path: &Path, key: Option<&str>) -> Result<Connection> {
+    let conn = crate::storage::open(db_path, key)?;
+    // Shell hooks may write while `prynt sync` holds the database
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS command_logs (
             id TEXT PRIMARY KEY,
@@ -23,21 +77,200 @@ pub fn init_db(db_path: &str) -> Result<Connection> {
         )",
         [],
     )?;
+    migrate_timing_columns(&conn)?;
+    if !has_column(&conn, "command_logs", "rerun_of")? {
+        // Set on commands started by `prynt rerun`, pointing at the original row
+        conn.execute("ALTER TABLE command_logs ADD COLUMN rerun_of TEXT", [])?;
+    }
+    conn.execute_batch(
+        "PRAGMA foreign_keys = ON;
+         CREATE INDEX IF NOT EXISTS idx_command_logs_started_at ON command_logs (started_at);
+         CREATE TABLE IF NOT EXISTS command_tags (
+            log_id TEXT NOT NULL REFERENCES command_logs (id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (log_id, tag)
+         );
+         CREATE INDEX IF NOT EXISTS idx_command_tags_tag ON command_tags (tag);
+         CREATE TABLE IF NOT EXISTS command_notes (
+            log_id TEXT PRIMARY KEY REFERENCES command_logs (id) ON DELETE CASCADE,
+            note TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS daily_rollups (
+            day TEXT NOT NULL,
+            cwd TEXT NOT NULL,
+            command TEXT NOT NULL,
+            runs INTEGER NOT NULL,
+            failures INTEGER NOT NULL,
+            duration_ns INTEGER NOT NULL,
+            min_duration_ns INTEGER NOT NULL,
+            max_duration_ns INTEGER NOT NULL,
+            PRIMARY KEY (day, cwd, command)
+         );
+         CREATE VIEW IF NOT EXISTS command_totals AS
+            SELECT cwd, command, COUNT(*) AS runs, SUM(exit_code != 0) AS failures, SUM(duration_ns) AS duration_ns,
+                   MIN(duration_ns) AS min_duration_ns, MAX(duration_ns) AS max_duration_ns
+            FROM command_logs GROUP BY cwd, command
+            UNION ALL
+            SELECT cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns FROM daily_rollups;
+         CREATE TABLE IF NOT EXISTS forgotten (
+            id TEXT PRIMARY KEY
+         );
+         CREATE TABLE IF NOT EXISTS sync_state (
+            target TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            key TEXT NOT NULL,
+            digest TEXT NOT NULL,
+            PRIMARY KEY (target, kind, key)
+         );
+         CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS snippets (
+            name TEXT PRIMARY KEY,
+            command TEXT NOT NULL,
+            description TEXT,
+            source_id TEXT,
+            created_at TEXT NOT NULL
+         );",
+    )?;
     Ok(conn)
 }
 
-pub fn insert_command_log(conn: &Connection, log: &CommandLog) -> Result<()> {
-    conn.execute(
-        "INSERT INTO command_logs (id, timestamp, cwd, command, exit_code, duration_secs)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Adds `started_at`, `ended_at` and `duration_ns` to databases created before
+/// they existed. Old rows only know their insert time (the end of the command)
+/// and a float duration, so the start time is derived from those.
+fn migrate_timing_columns(conn: &Connection) -> Result<()> {
+    if has_column(conn, "command_logs", "started_at")? {
+        return Ok(());
+    }
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "ALTER TABLE command_logs ADD COLUMN started_at TEXT;
+         ALTER TABLE command_logs ADD COLUMN ended_at TEXT;
+         ALTER TABLE command_logs ADD COLUMN duration_ns INTEGER;",
+    )?;
+    let mut stmt = tx.prepare("SELECT id, timestamp, duration_secs FROM command_logs")?;
+    let rows: Vec<(String, String, f64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_>>()?;
+    drop(stmt);
+    for (id, timestamp, duration_secs) in rows {
+        let ended_at = parse_ts(&timestamp);
+        let duration_ns = (duration_secs * 1_000_000_000.0) as i64;
+        let started_at = ended_at - Duration::nanoseconds(duration_ns);
+        tx.execute(
+            "UPDATE command_logs SET started_at = ?1, ended_at = ?2, duration_ns = ?3 WHERE id = ?4",
+            params![format_ts(&started_at), format_ts(&ended_at), duration_ns, id],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Inserts `log` unless a row with its id exists. Returns whether it was inserted.
+pub fn insert_command_log(conn: &Connection, log: &CommandLog) -> Result<bool> {
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO command_logs (id, timestamp, cwd, command, exit_code, duration_secs, started_at, ended_at, duration_ns)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         params![
             log.id,
-            log.timestamp.to_rfc3339(),
+            format_ts(&log.ended_at),
             log.cwd,
             log.command,
             log.exit_code,
-            log.duration_secs,
+            log.duration_secs(),
+            format_ts(&log.started_at),
+            format_ts(&log.ended_at),
+            log.duration_ns,
         ],
     )?;
+    Ok(inserted > 0)
+}
+
+/// Ids starting with `prefix`. At most two are returned, which is enough to
+/// tell a unique match from an ambiguous one.
+pub fn find_ids_by_prefix(conn: &Connection, prefix: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id FROM command_logs WHERE substr(id, 1, length(?1)) = ?1 LIMIT 2")?;
+    stmt.query_map([prefix], |row| row.get(0))?.collect()
+}
+
+pub fn add_tags(conn: &Connection, id: &str, tags: &[String]) -> Result<()> {
+    for tag in tags {
+        conn.execute("INSERT OR IGNORE INTO command_tags (log_id, tag) VALUES (?1, ?2)", params![id, tag])?;
+    }
+    Ok(())
+}
+
+pub fn remove_tags(conn: &Connection, id: &str, tags: &[String]) -> Result<()> {
+    for tag in tags {
+        conn.execute("DELETE FROM command_tags WHERE log_id = ?1 AND tag = ?2", params![id, tag])?;
+    }
+    Ok(())
+}
+
+/// Sets the note on a command, or removes it when `note` is `None`.
+pub fn set_note(conn: &Connection, id: &str, note: Option<&str>) -> Result<()> {
+    match note {
+        Some(note) => conn.execute(
+            "INSERT INTO command_notes (log_id, note) VALUES (?1, ?2)
+             ON CONFLICT (log_id) DO UPDATE SET note = excluded.note",
+            params![id, note],
+        )?,
+        None => conn.execute("DELETE FROM command_notes WHERE log_id = ?1", [id])?,
+    };
+    Ok(())
+}
+
+pub fn annotations(conn: &Connection, id: &str) -> Result<Annotations> {
+    let mut stmt = conn.prepare("SELECT tag FROM command_tags WHERE log_id = ?1 ORDER BY tag")?;
+    let tags = stmt.query_map([id], |row| row.get(0))?.collect::<Result<_>>()?;
+    let note = conn
+        .query_row("SELECT note FROM command_notes WHERE log_id = ?1", [id], |row| row.get(0))
+        .optional()?;
+    Ok(Annotations { tags, note, rerun_of: rerun_of(conn, id)? })
+}
+
+pub fn set_rerun_of(conn: &Connection, id: &str, original_id: &str) -> Result<()> {
+    conn.execute("UPDATE command_logs SET rerun_of = ?1 WHERE id = ?2", params![original_id, id])?;
+    Ok(())
+}
+
+pub fn rerun_of(conn: &Connection, id: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT rerun_of FROM command_logs WHERE id = ?1", [id], |row| row.get(0))
+}
+
+pub fn get_log(conn: &Connection, id: &str) -> Result<CommandLog> {
+    let query = format!("SELECT {} FROM command_logs WHERE id = ?1", LOG_COLUMNS);
+    conn.query_row(&query, [id], log_from_row)
+}
+
+/// Remembers deleted command ids so sync and restore never bring them back.
+pub fn mark_forgotten(conn: &Connection, ids: &[String]) -> Result<()> {
+    for id in ids {
+        conn.execute("INSERT OR IGNORE INTO forgotten (id) VALUES (?1)", [id])?;
+    }
+    Ok(())
+}
+
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0)).optional()
+}
+
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2) ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
     Ok(())
-} 
\ No newline at end of file
+}
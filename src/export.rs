@@ -0,0 +1,42 @@
+use crate::db::CommandLog;
+use crate::shell::Shell;
+
+/// `logs` in the shell's own history file format, oldest first as given.
+/// Returns bytes because zsh escapes some non-ASCII bytes.
+pub fn render(logs: &[CommandLog], shell: Shell) -> Vec<u8> {
+    let mut out = Vec::new();
+    for log in logs {
+        let started = log.started_at.timestamp();
+        match shell {
+            Shell::Bash => {
+                out.extend(format!("#{}\n{}\n", started, log.command).into_bytes());
+            }
+            Shell::Zsh => {
+                let elapsed = log.duration_ns / 1_000_000_000;
+                out.extend(format!(": {}:{};", started, elapsed).into_bytes());
+                out.extend(metafy(&log.command.replace('\n', "\\\n")));
+                out.push(b'\n');
+            }
+            Shell::Fish => {
+                let command = log.command.replace('\\', "\\\\").replace('\n', "\\n");
+                out.extend(format!("- cmd: {}\n  when: {}\n", command, started).into_bytes());
+            }
+        }
+    }
+    out
+}
+
+/// The reverse of zsh's unmetafy: NUL, its Meta byte 0x83 and its token bytes
+/// up to 0xa2 are written as 0x83 followed by the byte xor 0x20.
+fn metafy(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for &b in text.as_bytes() {
+        if b == 0 || (0x83..=0xa2).contains(&b) {
+            out.push(0x83);
+            out.push(b ^ 0x20);
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
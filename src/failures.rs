@@ -0,0 +1,161 @@
+use crate::db::CommandLog;
+use crate::session::project_root;
+use chrono::Duration;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct FailureRate {
+    pub runs: usize,
+    pub failures: usize,
+}
+
+impl FailureRate {
+    pub fn rate(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.failures as f64 / self.runs as f64 }
+    }
+}
+
+/// A failed command followed shortly by a successful, edited version of it.
+pub struct FixedBy {
+    pub failed: String,
+    pub fixed: String,
+    pub count: usize,
+}
+
+/// A command that keeps flipping between success and failure in one directory.
+pub struct Flaky {
+    pub command: String,
+    pub cwd: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub flips: usize,
+}
+
+pub struct FailureReport {
+    pub by_program: Vec<(String, FailureRate)>,
+    pub by_project: Vec<(String, FailureRate)>,
+    pub top_failing: Vec<(String, usize)>,
+    pub fixed_by: Vec<FixedBy>,
+    pub flaky: Vec<Flaky>,
+}
+
+/// The program and, when there is one, its subcommand: `git push`,
+/// `cargo test`, `ls`. Leading `VAR=value` assignments and `sudo` are skipped.
+pub fn program_key(command: &str) -> String {
+    let mut words = command.split_whitespace().skip_while(|w| (w.contains('=') && !w.starts_with('-')) || *w == "sudo");
+    let Some(program) = words.next() else { return String::new() };
+    match words.next() {
+        Some(sub) if sub.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') && !sub.starts_with('-') => {
+            format!("{} {}", program, sub)
+        }
+        _ => program.to_string(),
+    }
+}
+
+/// Edit distance between two strings in characters, where swapping two
+/// adjacent characters (`gti` → `git`) counts as a single edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j - 1] + cost).min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Whether `b` looks like an edited `a`: same program, or differing in at most
+/// a third of the characters.
+pub fn is_edit_of(a: &str, b: &str) -> bool {
+    if a == b {
+        return false;
+    }
+    let longest = a.chars().count().max(b.chars().count());
+    program_key(a) == program_key(b) || edit_distance(a, b) * 3 <= longest
+}
+
+/// Builds the report from `logs` ordered by start time. A retry counts as a fix
+/// when it starts no later than `retry_window` after the failure ended.
+pub fn analyze(logs: &[&CommandLog], retry_window: Duration, limit: usize) -> FailureReport {
+    let mut by_program: HashMap<String, FailureRate> = HashMap::new();
+    let mut by_project: HashMap<String, FailureRate> = HashMap::new();
+    let mut roots: HashMap<&str, String> = HashMap::new();
+    let mut failing: HashMap<&str, usize> = HashMap::new();
+    let mut fixes: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut outcomes: HashMap<(&str, &str), Vec<bool>> = HashMap::new();
+    for (i, log) in logs.iter().enumerate() {
+        let failed = log.exit_code != 0;
+        let root = roots.entry(&log.cwd).or_insert_with(|| project_root(&log.cwd)).clone();
+        for rate in [by_program.entry(program_key(&log.command)).or_default(), by_project.entry(root).or_default()] {
+            rate.runs += 1;
+            rate.failures += usize::from(failed);
+        }
+        outcomes.entry((&log.command, &log.cwd)).or_default().push(failed);
+        if !failed {
+            continue;
+        }
+        *failing.entry(&log.command).or_default() += 1;
+        if let Some(next) = logs.get(i + 1)
+            && next.exit_code == 0
+            && next.started_at - log.ended_at <= retry_window
+            && is_edit_of(&log.command, &next.command)
+        {
+            *fixes.entry((&log.command, &next.command)).or_default() += 1;
+        }
+    }
+
+    let mut flaky: Vec<Flaky> = outcomes
+        .into_iter()
+        .filter_map(|((command, cwd), results)| {
+            let flips = results.windows(2).filter(|w| w[0] != w[1]).count();
+            (flips >= 2).then(|| Flaky {
+                command: command.to_string(),
+                cwd: cwd.to_string(),
+                runs: results.len(),
+                failures: results.iter().filter(|f| **f).count(),
+                flips,
+            })
+        })
+        .collect();
+    flaky.sort_by(|a, b| b.flips.cmp(&a.flips).then_with(|| a.command.cmp(&b.command)));
+    flaky.truncate(limit);
+
+    let mut fixed_by: Vec<FixedBy> = fixes
+        .into_iter()
+        .map(|((failed, fixed), count)| FixedBy { failed: failed.to_string(), fixed: fixed.to_string(), count })
+        .collect();
+    fixed_by.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.failed.cmp(&b.failed)));
+    fixed_by.truncate(limit);
+
+    let mut top_failing: Vec<(String, usize)> = failing.into_iter().map(|(c, n)| (c.to_string(), n)).collect();
+    top_failing.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_failing.truncate(limit);
+
+    FailureReport {
+        by_program: sorted_rates(by_program, limit),
+        by_project: sorted_rates(by_project, limit),
+        top_failing,
+        fixed_by,
+        flaky,
+    }
+}
+
+/// Keeps entries with at least one failure, highest failure rate first.
+fn sorted_rates(rates: HashMap<String, FailureRate>, limit: usize) -> Vec<(String, FailureRate)> {
+    let mut rates: Vec<_> = rates.into_iter().filter(|(_, r)| r.failures > 0).collect();
+    rates.sort_by(|a, b| {
+        b.1.rate().partial_cmp(&a.1.rate()).unwrap().then_with(|| b.1.runs.cmp(&a.1.runs)).then_with(|| a.0.cmp(&b.0))
+    });
+    rates.truncate(limit);
+    rates
+}
@@ -0,0 +1,196 @@
+use crate::db::{self, Annotations, CommandLog, LOG_COLUMNS};
+use crate::query::{Filter, Order, TimeRange};
+use crate::store::{self, CommandCount, FolderTotal, Query, Store, Totals};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OptionalExtension, Result};
+use std::path::{Path, PathBuf};
+
+/// SQL condition for rows that are not `prynt` invocations, as `CommandLog::is_prynt` decides.
+const NOT_PRYNT: &str = "(ltrim(command) <> 'prynt' AND ltrim(command) NOT GLOB 'prynt *')";
+
+/// A prynt history database: the commands the shell hooks logged, with their
+/// tags and notes, plus the daily totals kept for pruned commands. This is the
+/// `Store` the CLI uses unless `database.backend` says otherwise.
+pub struct HistoryStore {
+    conn: Connection,
+}
+
+impl HistoryStore {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        HistoryStore::open_with_key(path, None)
+    }
+
+    /// Opens a database encrypted by `prynt db encrypt` with its SQLCipher `key`.
+    pub fn open_with_key(path: impl AsRef<Path>, key: Option<&str>) -> Result<Self> {
+        Ok(HistoryStore { conn: db::init_db(path.as_ref(), key)? })
+    }
+
+    /// An empty history that only lives in memory.
+    pub fn open_in_memory() -> Result<Self> {
+        HistoryStore::open(":memory:")
+    }
+
+    /// Where the `prynt` CLI keeps its history: `~/.context/prynt.sqlite`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".context/prynt.sqlite"))
+    }
+
+    /// The underlying connection, for the modules that work on it directly.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn connection_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+}
+
+impl Store for HistoryStore {
+    fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    fn insert(&self, log: &CommandLog) -> store::Result<bool> {
+        Ok(db::insert_command_log(&self.conn, log)?)
+    }
+
+    fn insert_all(&self, logs: &[CommandLog]) -> store::Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut inserted = 0;
+        for log in logs {
+            inserted += db::insert_command_log(&tx, log)? as usize;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    fn logs(&self, filter: &Filter, order: Order, limit: Option<usize>) -> store::Result<Vec<CommandLog>> {
+        let (condition, mut params) = where_clause(filter);
+        let order = match order {
+            Order::OldestFirst => "ASC",
+            Order::NewestFirst => "DESC",
+        };
+        params.push(Value::Integer(limit.map_or(-1, |n| n as i64)));
+        let query = format!("SELECT {} FROM command_logs WHERE {} ORDER BY started_at {} LIMIT ?", LOG_COLUMNS, condition, order);
+        let mut stmt = self.conn.prepare(&query)?;
+        Ok(stmt.query_map(params_from_iter(params), db::log_from_row)?.collect::<Result<_>>()?)
+    }
+
+    /// Deletes the commands without keeping daily totals, and remembers their
+    /// ids so sync and restore never bring them back.
+    fn delete(&self, ids: &[String]) -> store::Result<usize> {
+        let deleted = crate::retention::prune(&self.conn, ids, crate::retention::Rollup::None)?;
+        db::mark_forgotten(&self.conn, ids)?;
+        Ok(deleted)
+    }
+
+    /// Deletes every command and the daily totals of pruned ones.
+    fn clear(&self) -> store::Result<()> {
+        self.conn.execute_batch("DELETE FROM command_logs; DELETE FROM daily_rollups;")?;
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> store::Result<Option<CommandLog>> {
+        Ok(db::get_log(&self.conn, id).optional()?)
+    }
+
+    fn ids_with_prefix(&self, prefix: &str) -> store::Result<Vec<String>> {
+        Ok(db::find_ids_by_prefix(&self.conn, prefix)?)
+    }
+
+    fn annotations(&self, id: &str) -> store::Result<Annotations> {
+        Ok(db::annotations(&self.conn, id)?)
+    }
+
+    /// Totals of the commands matching `filter`. Without a time range or tag
+    /// they include the daily totals of pruned commands, which have neither.
+    fn totals(&self, filter: &Filter) -> store::Result<Totals> {
+        let (source, params) = totals_source(filter);
+        let query = format!(
+            "SELECT COALESCE(SUM(runs), 0), COALESCE(SUM(failures), 0), COALESCE(SUM(duration_ns), 0), MIN(min_duration_ns), MAX(max_duration_ns) FROM ({})",
+            source
+        );
+        Ok(self.conn.query_row(&query, params_from_iter(params), |row| {
+            Ok(Totals { runs: row.get(0)?, failures: row.get(1)?, duration_ns: row.get(2)?, min_duration_ns: row.get(3)?, max_duration_ns: row.get(4)? })
+        })?)
+    }
+
+    fn top_commands(&self, filter: &Filter, limit: usize) -> store::Result<Vec<CommandCount>> {
+        let (source, mut params) = totals_source(filter);
+        params.push(Value::Integer(limit as i64));
+        let query = format!("SELECT command, SUM(runs) FROM ({}) GROUP BY command ORDER BY SUM(runs) DESC, command LIMIT ?", source);
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = stmt.query_map(params_from_iter(params), |row| Ok(CommandCount { command: row.get(0)?, runs: row.get(1)? }))?;
+        Ok(rows.collect::<Result<_>>()?)
+    }
+
+    fn folders(&self, filter: &Filter) -> store::Result<Vec<FolderTotal>> {
+        let (source, params) = totals_source(filter);
+        let query = format!("SELECT cwd, SUM(runs), SUM(duration_ns) FROM ({}) GROUP BY cwd ORDER BY SUM(runs) DESC, cwd", source);
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = stmt.query_map(params_from_iter(params), |row| Ok(FolderTotal { cwd: row.get(0)?, runs: row.get(1)?, duration_ns: row.get(2)? }))?;
+        Ok(rows.collect::<Result<_>>()?)
+    }
+
+    fn sqlite(&self) -> Option<&Connection> {
+        Some(&self.conn)
+    }
+
+    fn sqlite_mut(&mut self) -> Option<&mut Connection> {
+        Some(&mut self.conn)
+    }
+}
+
+/// `text` as a LIKE pattern matching it anywhere, with `\` as escape character.
+fn like_anywhere(text: &str) -> String {
+    format!("%{}%", text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
+
+/// The SQL condition for `filter` on `command_logs` and its parameters. Time
+/// range and tag conditions only appear when they are set.
+fn where_clause(filter: &Filter) -> (String, Vec<Value>) {
+    let mut conditions = vec!["1".to_string()];
+    let mut params = Vec::new();
+    if let Some(start) = filter.range.start {
+        conditions.push("started_at >= ?".to_string());
+        params.push(Value::Text(db::format_ts(&start)));
+    }
+    if let Some(end) = filter.range.end {
+        conditions.push("started_at < ?".to_string());
+        params.push(Value::Text(db::format_ts(&end)));
+    }
+    if let Some(tag) = &filter.tag {
+        conditions.push("id IN (SELECT log_id FROM command_tags WHERE tag = ?)".to_string());
+        params.push(Value::Text(tag.clone()));
+    }
+    for (column, text) in [("command", &filter.command_contains), ("cwd", &filter.cwd_contains)] {
+        if let Some(text) = text {
+            conditions.push(format!("{} LIKE ? ESCAPE '\\'", column));
+            params.push(Value::Text(like_anywhere(text)));
+        }
+    }
+    if let Some(dir) = &filter.under {
+        let root = dir.trim_end_matches('/');
+        conditions.push("(cwd = ? OR substr(cwd, 1, length(?) + 1) = ? || '/')".to_string());
+        params.extend([Value::Text(root.to_string()), Value::Text(root.to_string()), Value::Text(root.to_string())]);
+    }
+    if filter.exclude_prynt {
+        conditions.push(NOT_PRYNT.to_string());
+    }
+    (conditions.join(" AND "), params)
+}
+
+/// Rows shaped like the `command_totals` view for the commands matching `filter`.
+fn totals_source(filter: &Filter) -> (String, Vec<Value>) {
+    let (condition, params) = where_clause(filter);
+    if filter.range == TimeRange::all() && filter.tag.is_none() {
+        return (format!("SELECT * FROM command_totals WHERE {}", condition), params);
+    }
+    let source = format!(
+        "SELECT cwd, command, 1 AS runs, exit_code != 0 AS failures, duration_ns, duration_ns AS min_duration_ns, duration_ns AS max_duration_ns
+         FROM command_logs WHERE {}",
+        condition
+    );
+    (source, params)
+}
@@ -0,0 +1,280 @@
+use crate::db::{self, CommandLog};
+use crate::query::TimeRange;
+use crate::store::{self, Store};
+use chrono::{DateTime, Duration, Local, Timelike};
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Where history can be imported from.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Source {
+    Bash,
+    Zsh,
+    Fish,
+    Atuin,
+    Mcfly,
+}
+
+impl Source {
+    fn name(self) -> &'static str {
+        match self {
+            Source::Bash => "bash",
+            Source::Zsh => "zsh",
+            Source::Fish => "fish",
+            Source::Atuin => "atuin",
+            Source::Mcfly => "mcfly",
+        }
+    }
+
+    /// Where the tool keeps its history by default.
+    pub fn default_path(self) -> Option<PathBuf> {
+        let home = dirs::home_dir()?;
+        let data = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from).unwrap_or_else(|| home.join(".local/share"));
+        Some(match self {
+            Source::Bash => home.join(".bash_history"),
+            Source::Zsh => std::env::var_os("HISTFILE").map(PathBuf::from).unwrap_or_else(|| home.join(".zsh_history")),
+            Source::Fish => data.join("fish/fish_history"),
+            Source::Atuin => data.join("atuin/history.db"),
+            Source::Mcfly if cfg!(target_os = "macos") => dirs::data_dir()?.join("McFly/history.db"),
+            Source::Mcfly => data.join("mcfly/history.db"),
+        })
+    }
+}
+
+/// One history entry with whatever its source recorded.
+pub struct Entry {
+    pub command: String,
+    pub started_at: Option<DateTime<Local>>,
+    pub duration_ns: Option<i64>,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+}
+
+impl Entry {
+    fn new(command: String, started_at: Option<DateTime<Local>>) -> Self {
+        Entry { command, started_at, duration_ns: None, cwd: None, exit_code: None }
+    }
+}
+
+pub struct ImportReport {
+    pub imported: usize,
+    pub duplicates: usize,
+}
+
+/// Reads every entry from the history file at `path`, oldest first.
+pub fn read(source: Source, path: &Path) -> Result<Vec<Entry>, Box<dyn Error>> {
+    Ok(match source {
+        Source::Bash => parse_bash(&String::from_utf8_lossy(&std::fs::read(path)?)),
+        Source::Zsh => parse_zsh(&String::from_utf8_lossy(&unmetafy(&std::fs::read(path)?))),
+        Source::Fish => parse_fish(&String::from_utf8_lossy(&std::fs::read(path)?)),
+        Source::Atuin => read_atuin(path)?,
+        Source::Mcfly => read_mcfly(path)?,
+    })
+}
+
+fn from_unix_secs(secs: i64) -> Option<DateTime<Local>> {
+    DateTime::from_timestamp(secs, 0).map(|ts| ts.with_timezone(&Local))
+}
+
+/// One command per line, or with `HISTTIMEFORMAT` set, `#<epoch>` lines each
+/// followed by one (possibly multi-line) command.
+fn parse_bash(text: &str) -> Vec<Entry> {
+    let timestamp = |line: &str| line.strip_prefix('#').filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())).and_then(|n| n.parse().ok());
+    let timed = text.lines().any(|line| timestamp(line).is_some());
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in text.lines() {
+        if let Some(secs) = timestamp(line) {
+            entries.push(Entry::new(String::new(), from_unix_secs(secs)));
+        } else if timed && let Some(entry) = entries.last_mut().filter(|entry| entry.started_at.is_some()) {
+            if !entry.command.is_empty() {
+                entry.command.push('\n');
+            }
+            entry.command.push_str(line);
+        } else {
+            entries.push(Entry::new(line.to_string(), None));
+        }
+    }
+    entries.retain(|entry| !entry.command.trim().is_empty());
+    entries
+}
+
+/// zsh stores bytes that clash with its own tokens as 0x83 followed by the
+/// byte xor 0x20.
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        match b {
+            0x83 => out.extend(iter.next().map(|next| next ^ 0x20)),
+            _ => out.push(b),
+        }
+    }
+    out
+}
+
+/// Plain lines or the `EXTENDED_HISTORY` format `: <start>:<elapsed>;<command>`,
+/// where a trailing backslash continues the command on the next line.
+fn parse_zsh(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let mut entry = match line.strip_prefix(": ").and_then(|rest| rest.split_once(';')) {
+            Some((meta, command)) if meta.contains(':') => {
+                let (start, elapsed) = meta.split_once(':').unwrap();
+                let mut entry = Entry::new(command.to_string(), start.trim().parse().ok().and_then(from_unix_secs));
+                // A malformed elapsed time too large to count in nanoseconds is dropped
+                entry.duration_ns = elapsed.trim().parse::<i64>().ok().and_then(|secs| secs.checked_mul(1_000_000_000));
+                entry
+            }
+            _ => Entry::new(line.to_string(), None),
+        };
+        while entry.command.ends_with('\\') {
+            entry.command.pop();
+            entry.command.push('\n');
+            match lines.next() {
+                Some(next) => entry.command.push_str(next),
+                None => break,
+            }
+        }
+        if !entry.command.trim().is_empty() {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// fish's YAML-like `fish_history`: `- cmd: ...` followed by `  when: <epoch>`.
+fn parse_fish(text: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in text.lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            entries.push(Entry::new(unescape_fish(command), None));
+        } else if let Some(when) = line.strip_prefix("  when: ")
+            && let Some(entry) = entries.last_mut()
+        {
+            entry.started_at = when.trim().parse().ok().and_then(from_unix_secs);
+        }
+    }
+    entries
+}
+
+fn unescape_fish(command: &str) -> String {
+    let mut out = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn open_read_only(path: &Path) -> rusqlite::Result<Connection> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+}
+
+/// atuin records start times and durations in nanoseconds (-1 when unknown).
+fn read_atuin(path: &Path) -> rusqlite::Result<Vec<Entry>> {
+    let conn = open_read_only(path)?;
+    let not_deleted = if db::has_column(&conn, "history", "deleted_at")? { "WHERE deleted_at IS NULL" } else { "" };
+    let mut stmt = conn.prepare(&format!("SELECT command, timestamp, duration, exit, cwd FROM history {} ORDER BY timestamp", not_deleted))?;
+    stmt.query_map([], |row| {
+        Ok(Entry {
+            command: row.get(0)?,
+            started_at: Some(DateTime::from_timestamp_nanos(row.get(1)?).with_timezone(&Local)),
+            duration_ns: row.get::<_, i64>(2).ok().filter(|ns| *ns >= 0),
+            exit_code: row.get(3)?,
+            cwd: row.get(4)?,
+        })
+    })?
+    .collect()
+}
+
+/// McFly records start times in seconds and no durations.
+fn read_mcfly(path: &Path) -> rusqlite::Result<Vec<Entry>> {
+    let conn = open_read_only(path)?;
+    let mut stmt = conn.prepare("SELECT cmd, when_run, exit_code, dir FROM commands ORDER BY id")?;
+    stmt.query_map([], |row| {
+        Ok(Entry {
+            command: row.get(0)?,
+            started_at: row.get::<_, Option<i64>>(1)?.and_then(from_unix_secs),
+            duration_ns: None,
+            exit_code: row.get(2)?,
+            cwd: row.get(3)?,
+        })
+    })?
+    .collect()
+}
+
+/// Adds `entries` to the log. Entries without a time get the time of the
+/// entry before them; those before the first timed entry are dated a second
+/// apart, ending just before the earliest time known from the file or the
+/// log, or before `fallback_time` when neither has one, so they stay out of
+/// recent reports. Entries without a directory get `fallback_cwd`. Importing
+/// the same history again adds nothing, and timed entries are skipped when
+/// the same command was already logged in that second.
+pub fn import(store: &dyn Store, source: Source, entries: &[Entry], fallback_time: DateTime<Local>, fallback_cwd: &str) -> store::Result<ImportReport> {
+    let times = || entries.iter().filter_map(|entry| entry.started_at);
+    let leading = entries.iter().take_while(|entry| entry.started_at.is_none()).count();
+    let earliest = match store.query().first()? {
+        Some(oldest) => times().min().map_or(oldest.started_at, |first| first.min(oldest.started_at)),
+        None => times().min().unwrap_or(fallback_time),
+    };
+    let leading_start = earliest.with_nanosecond(0).unwrap() - Duration::seconds(leading as i64);
+    // Commands already logged in the seconds the timed entries were started, by second and command
+    let logged = match times().min().zip(times().max()) {
+        Some((first, last)) => store.query().range(TimeRange::between(first.with_nanosecond(0).unwrap(), last + Duration::seconds(1))).fetch()?,
+        None => Vec::new(),
+    };
+    let mut logged_at: HashMap<(i64, &str), Vec<&str>> = HashMap::new();
+    for log in &logged {
+        logged_at.entry((log.started_at.timestamp(), &log.command)).or_default().push(&log.id);
+    }
+    let mut occurrences: HashMap<(Option<i64>, &str), usize> = HashMap::new();
+    let mut logs = Vec::new();
+    let mut duplicates = 0;
+    let mut last_time = leading_start;
+    for (i, entry) in entries.iter().enumerate() {
+        let started_at = match entry.started_at {
+            Some(started_at) => started_at,
+            None if i < leading => leading_start + Duration::seconds(i as i64),
+            None => last_time,
+        };
+        last_time = started_at;
+        let secs = entry.started_at.map(|ts| ts.timestamp());
+        // Stable across re-imports of a file that has grown since
+        let n = occurrences.entry((secs, &entry.command)).or_default();
+        *n += 1;
+        let key = format!("{}\0{}\0{}\0{}", source.name(), secs.map(|s| s.to_string()).unwrap_or_default(), entry.command, n);
+        let id = uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, key.as_bytes()).to_string();
+        // Checked against the rows from before this import, so repeats within the file are kept
+        if let Some(secs) = secs
+            && logged_at.get(&(secs, entry.command.as_str())).is_some_and(|ids| ids.iter().any(|logged| *logged != id))
+        {
+            duplicates += 1;
+            continue;
+        }
+        let duration_ns = entry.duration_ns.unwrap_or(0);
+        logs.push(CommandLog {
+            id,
+            started_at,
+            ended_at: started_at + Duration::nanoseconds(duration_ns),
+            cwd: entry.cwd.clone().unwrap_or_else(|| fallback_cwd.to_string()),
+            command: entry.command.clone(),
+            exit_code: entry.exit_code.unwrap_or(0),
+            duration_ns,
+        });
+    }
+    let imported = store.insert_all(&logs)?;
+    Ok(ImportReport { imported, duplicates: duplicates + logs.len() - imported })
+}
@@ -0,0 +1,46 @@
+use crate::suggest::frecency_weight;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct Candidate {
+    pub dir: String,
+    pub score: f64,
+}
+
+/// Directories matching `keywords`, best first, scored by the frecency of the
+/// commands run in them. `visits` is every logged (cwd, start time) pair.
+pub fn rank(visits: &[(String, DateTime<Local>)], keywords: &[String], now: DateTime<Local>, exclude: Option<&str>) -> Vec<Candidate> {
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+    for (dir, started_at) in visits {
+        *scores.entry(dir).or_insert(0.0) += frecency_weight(now - *started_at);
+    }
+    let mut candidates: Vec<Candidate> = scores
+        .into_iter()
+        .filter(|(dir, _)| Some(*dir) != exclude && matches(dir, keywords) && Path::new(dir).is_dir())
+        .map(|(dir, score)| Candidate { dir: dir.to_string(), score })
+        .collect();
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap().then_with(|| a.dir.cmp(&b.dir)));
+    candidates
+}
+
+/// Keywords must appear in the path in order (case-insensitively), and the
+/// last one must be part of the final path component.
+fn matches(dir: &str, keywords: &[String]) -> bool {
+    let path = dir.to_lowercase();
+    let mut rest = path.as_str();
+    for keyword in keywords {
+        let keyword = keyword.to_lowercase();
+        match rest.find(&keyword) {
+            Some(i) => rest = &rest[i + keyword.len()..],
+            None => return false,
+        }
+    }
+    match keywords.last() {
+        Some(last) => {
+            let name = path.rsplit('/').next().unwrap_or_default();
+            name.contains(&last.to_lowercase())
+        }
+        None => true,
+    }
+}
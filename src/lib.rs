@@ -0,0 +1,66 @@
+//! Read and write prynt command history from Rust.
+//!
+//! [`HistoryStore`] opens a history database (the one the `prynt` CLI uses is
+//! at [`HistoryStore::default_path`]), adds [`CommandLog`]s and queries them
+//! with a [`Filter`] or the query builder. It implements the [`Store`] trait,
+//! as do [`JsonlStore`], an append-only file for CI and other short-lived
+//! environments, and [`MemoryStore`] for tests. [`report::PeriodReport`]
+//! builds the summaries `prynt today` and `prynt weekly` print.
+//!
+//! ```
+//! use chrono::{Duration, Local};
+//! use prynt::{CommandLog, HistoryStore, Store};
+//!
+//! let store = HistoryStore::open_in_memory()?;
+//! let now = Local::now();
+//! store.insert(&CommandLog {
+//!     id: "1".to_string(),
+//!     started_at: now - Duration::seconds(3),
+//!     ended_at: now,
+//!     cwd: "/home/me/project".to_string(),
+//!     command: "cargo build".to_string(),
+//!     exit_code: 0,
+//!     duration_ns: 3_000_000_000,
+//! })?;
+//!
+//! let recent = store.query().since(now - Duration::hours(1)).command_contains("cargo").fetch()?;
+//! assert_eq!(recent.len(), 1);
+//! assert_eq!(store.query().exclude_prynt().totals()?.runs, 1);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod clock;
+pub mod logger;
+pub mod db;
+pub mod config;
+pub mod session;
+pub mod timesheet;
+pub mod runner;
+pub mod snippets;
+pub mod suggest;
+pub mod jump;
+pub mod failures;
+pub mod shell;
+pub mod typos;
+pub mod aliases;
+pub mod workflows;
+pub mod timespec;
+pub mod retention;
+pub mod backup;
+pub mod import;
+pub mod export;
+pub mod archive;
+pub mod sync;
+pub mod serve;
+pub mod crypto;
+pub mod storage;
+pub mod team;
+pub mod query;
+pub mod history;
+pub mod report;
+pub mod store;
+
+pub use db::CommandLog;
+pub use history::HistoryStore;
+pub use query::{Filter, Order, TimeRange};
+pub use store::{CommandCount, FolderTotal, JsonlStore, MemoryStore, Query, Store, Totals};
@@ -1,17 +1,32 @@
-
-
 use crate::db::CommandLog;
-use rusqlite::Connection;
-use chrono::Local;
+use crate::store::{self, Store};
+use chrono::{DateTime, Duration, Local};
 
-pub fn log_command(conn: &Connection, command: String, cwd: String, exit_code: i32, duration_secs: f64) {
+/// Logs a finished command. Hooks that send `started_at_ns` (nanoseconds since
+/// the Unix epoch) get exact start/end times; older hooks only send a float
+/// duration, in which case the command is assumed to have just ended.
+/// Returns the id of the new row.
+pub fn log_command(store: &dyn Store, command: String, cwd: String, exit_code: i32, duration_secs: f64, started_at_ns: Option<i64>, duration_ns: Option<i64>) -> store::Result<String> {
+    let duration_ns = duration_ns.unwrap_or((duration_secs * 1_000_000_000.0) as i64).max(0);
+    let (started_at, ended_at) = match started_at_ns {
+        Some(ns) => {
+            let started_at = DateTime::from_timestamp_nanos(ns).with_timezone(&Local);
+            (started_at, started_at + Duration::nanoseconds(duration_ns))
+        }
+        None => {
+            let ended_at = crate::clock::now();
+            (ended_at - Duration::nanoseconds(duration_ns), ended_at)
+        }
+    };
     let log = CommandLog {
         id: uuid::Uuid::new_v4().to_string(),
-        timestamp: Local::now(),
+        started_at,
+        ended_at,
         cwd,
         command,
         exit_code,
-        duration_secs,
+        duration_ns,
     };
-    crate::db::insert_command_log(conn, &log).expect("Failed to insert log");
+    store.insert(&log)?;
+    Ok(log.id)
 }
@@ -1,9 +1,13 @@
-mod logger;
-mod db;
+mod prompt;
 
 use clap::{Parser, Subcommand};
-use chrono::{Local, DateTime};
-use db::{init_db, CommandLog};
+use chrono::{Local, DateTime, Duration};
+use prynt::report::PeriodReport;
+use prynt::{
+    aliases, archive, backup, clock, config, crypto, db, export, failures, import, jump, logger, retention, runner, serve, session, shell, snippets, storage,
+    suggest, sync, team, timesheet, timespec, typos, workflows, CommandLog, Filter, HistoryStore, JsonlStore, Order, Store, TimeRange,
+};
+use rusqlite::Connection;
 
 #[derive(Parser)]
 #[command(name = "prynt")]
@@ -21,6 +25,12 @@ enum Commands {
         cwd: String,
         exit_code: i32,
         duration_secs: f64,
+        /// Start time in nanoseconds since the Unix epoch
+        #[arg(long)]
+        started_at_ns: Option<i64>,
+        /// Exact duration in nanoseconds (takes precedence over duration_secs)
+        #[arg(long)]
+        duration_ns: Option<i64>,
     },
     /// Show complete command history
     Log {
@@ -30,6 +40,9 @@ enum Commands {
         /// View logs with a pager (less)
         #[arg(long)]
         less: bool,
+        /// Only show commands with this tag
+        #[arg(long)]
+        tag: Option<String>,
     },
     /// Show commands from the last 24 hours
     Today {
@@ -39,6 +52,12 @@ enum Commands {
         /// Export in markdown format
         #[arg(long)]
         markdown: bool,
+        /// Minutes without commands that end a work session (overrides config)
+        #[arg(long)]
+        idle_gap: Option<u64>,
+        /// Only include commands with this tag
+        #[arg(long)]
+        tag: Option<String>,
     },
     /// Show commands from the last 7 days
     Weekly {
@@ -48,6 +67,30 @@ enum Commands {
         /// Export in markdown format
         #[arg(long)]
         markdown: bool,
+        /// Minutes without commands that end a work session (overrides config)
+        #[arg(long)]
+        idle_gap: Option<u64>,
+        /// Only include commands with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Export active time per day and project as a timesheet
+    Timesheet {
+        /// Cover the last 7 days instead of the last 24 hours
+        #[arg(long)]
+        week: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = timesheet::TimesheetFormat::Csv)]
+        format: timesheet::TimesheetFormat,
+        /// Round active time up to this many minutes (overrides config, 0 disables)
+        #[arg(long)]
+        round: Option<u64>,
+        /// Minutes without commands that end a work session (overrides config)
+        #[arg(long)]
+        idle_gap: Option<u64>,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
     },
     /// Show summary for a specific project/folder
     Summary {
@@ -55,6 +98,123 @@ enum Commands {
     },
     /// Clear all command logs
     Clear,
+    /// Import history from another shell or history tool
+    Import {
+        #[arg(long, value_enum)]
+        from: import::Source,
+        /// History file or database (defaults to the tool's usual location)
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+    },
+    /// Write history in a shell's native history file format
+    Export {
+        #[arg(long, value_enum)]
+        to: shell::Shell,
+        /// Only commands run in this directory or below it
+        #[arg(long)]
+        project: Option<std::path::PathBuf>,
+        /// Only commands started after this time, e.g. 30d or 2024-05-01
+        #[arg(long, value_parser = timespec::time_arg)]
+        since: Option<DateTime<Local>>,
+        /// Only commands started before this time
+        #[arg(long, value_parser = timespec::time_arg)]
+        until: Option<DateTime<Local>>,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Write all commands, tags, notes and snippets to a compressed archive
+    Dump {
+        /// Archive to write, conventionally ending in .ndjson.gz
+        output: std::path::PathBuf,
+    },
+    /// Merge an archive from `prynt dump` into this history
+    Restore {
+        archive: std::path::PathBuf,
+    },
+    /// Exchange history with your other machines through a shared folder or `prynt serve`
+    Sync {
+        /// Shared folder or http:// URL of a `prynt serve` instance (defaults to sync.target)
+        target: Option<String>,
+    },
+    /// Run a sync server that stores change logs for `prynt sync`
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8731")]
+        listen: String,
+        /// Folder to keep change logs in (defaults to ~/.context/server)
+        #[arg(long)]
+        dir: Option<std::path::PathBuf>,
+        /// Also collect `prynt share` uploads and serve the team dashboard at /team
+        #[arg(long)]
+        team: bool,
+    },
+    /// Share anonymized per-program statistics with your team's server
+    Share {
+        /// URL of a `prynt serve --team` instance (defaults to team.url)
+        #[arg(long)]
+        team_url: Option<String>,
+        /// Share the last N days
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+        /// Print what would be sent instead of sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manage the key that encrypts dumps and synced history
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
+    },
+    /// Create, list and restore backups of the history database
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Encrypt the history database on disk, or store it in plain text again
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Permanently delete specific commands, e.g. one with a pasted password
+    #[command(group(clap::ArgGroup::new("selection").required(true).multiple(true).args(["ids", "last", "pattern", "since"])))]
+    Forget {
+        /// Ids of the commands to delete (unique prefixes work)
+        ids: Vec<String>,
+        /// The most recent command
+        #[arg(long, conflicts_with_all = ["ids", "pattern", "since"])]
+        last: bool,
+        /// Commands matching this regular expression
+        #[arg(long = "match")]
+        pattern: Option<regex::Regex>,
+        /// Only commands started within this long ago, e.g. 1h
+        #[arg(long, value_parser = timespec::duration_arg)]
+        since: Option<Duration>,
+        /// Pick which of the matching commands to delete
+        #[arg(long, short)]
+        interactive: bool,
+        /// Skip the confirmation prompt
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Delete old or unwanted commands, keeping their daily totals for reports
+    Prune {
+        /// Only commands started longer ago than this, e.g. 30d (defaults to retention.keep_raw_days)
+        #[arg(long, value_parser = timespec::duration_arg)]
+        older_than: Option<Duration>,
+        /// Only commands run in directories matching this glob, e.g. '/tmp/*'
+        #[arg(long)]
+        cwd: Option<String>,
+        /// Only commands matching this regular expression
+        #[arg(long = "match")]
+        pattern: Option<regex::Regex>,
+        /// Show what would be pruned without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(long, short)]
+        yes: bool,
+    },
     /// Show top N most used commands
     Top {
         #[arg(long, default_value_t = 10)]
@@ -65,6 +225,139 @@ enum Commands {
     /// Search history for commands matching a pattern
     Search {
         pattern: String,
+        /// Only show commands with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Pick results by number and tag them
+        #[arg(long)]
+        interactive: bool,
+    },
+    /// Add tags to a logged command (ids may be shortened to a unique prefix)
+    Tag {
+        id: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Remove the given tags instead of adding them
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Attach a note to a logged command, replacing any previous note
+    Note {
+        id: String,
+        #[arg(required_unless_present = "clear")]
+        text: Option<String>,
+        /// Remove the note instead
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+    /// Run a logged command again: the latest command matching a pattern, or else the one with this id
+    Rerun {
+        target: String,
+        /// Print what would run without running it
+        #[arg(long)]
+        dry_run: bool,
+        /// Run in the current directory instead of the original one
+        #[arg(long)]
+        here: bool,
+        /// Skip the confirmation prompt
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Suggest commands you usually run in this directory
+    Suggest {
+        /// Directory to suggest for (defaults to the current one)
+        #[arg(long)]
+        cwd: Option<String>,
+        /// The command just run, to rank likely follow-ups higher (defaults to the last logged one)
+        #[arg(long)]
+        last: Option<String>,
+        /// Only suggest commands starting with this text
+        #[arg(long)]
+        prefix: Option<String>,
+        #[arg(long, default_value_t = 5)]
+        limit: usize,
+        /// Print bare commands, one per line, for shell plugins
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Print the best matching directory you have worked in (used by `j` and `ji`)
+    Jump {
+        keywords: Vec<String>,
+        /// Pick from the top matches instead of taking the best one
+        #[arg(long)]
+        interactive: bool,
+        /// List the top matches with their scores
+        #[arg(long)]
+        list: bool,
+    },
+    /// Show failure rates, most failing commands, fixes and flaky commands
+    Failures {
+        /// Only look at the last N days
+        #[arg(long)]
+        days: Option<i64>,
+        /// Seconds within which a successful edited retry counts as the fix
+        #[arg(long, default_value_t = 120)]
+        within: i64,
+        /// Entries to show per section
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Show your most common typos and optionally print aliases that fix them
+    Typos {
+        /// Seconds within which a successful retry counts as the correction
+        #[arg(long, default_value_t = 60)]
+        within: i64,
+        /// Print shell aliases for mistyped program names instead of a report
+        #[arg(long, value_enum)]
+        aliases: Option<shell::Shell>,
+        /// Only make aliases for typos seen at least this often
+        #[arg(long, default_value_t = 2)]
+        min_count: usize,
+    },
+    /// Suggest aliases and shell functions for repetitive commands
+    Aliases {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+    /// Find command sequences you repeat within work sessions
+    Workflows {
+        /// Only show sequences seen at least this often
+        #[arg(long, default_value_t = 3)]
+        min_count: usize,
+        /// Longest sequence to look for
+        #[arg(long, default_value_t = 5)]
+        max_len: usize,
+        /// Minutes without commands that end a work session (overrides config)
+        #[arg(long)]
+        idle_gap: Option<u64>,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Save workflow number N from the list as a shell script
+        #[arg(long)]
+        save: Option<usize>,
+        /// Where to write the script saved with --save
+        #[arg(long, requires = "save")]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Save a logged command as a named snippet
+    Save {
+        id: String,
+        /// Name to save the snippet under
+        #[arg(long)]
+        name: String,
+        /// Store this command instead, e.g. the original with {{placeholders}}
+        #[arg(long)]
+        command: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+        /// Replace an existing snippet with the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Manage saved snippets
+    Snippets {
+        #[command(subcommand)]
+        action: SnippetAction,
     },
     /// Show overall productivity stats
     Stats,
@@ -72,42 +365,193 @@ enum Commands {
     Init,
 }
 
+#[derive(Subcommand)]
+enum SnippetAction {
+    /// List saved snippets
+    List,
+    /// Show a snippet and its placeholders
+    Show { name: String },
+    /// Fill in placeholders and run a snippet in the current directory
+    Run {
+        name: String,
+        /// Placeholder value as key=value (asked for interactively otherwise)
+        #[arg(long = "set")]
+        values: Vec<String>,
+        /// Print the filled-in command without running it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete a snippet
+    Rm { name: String },
+    /// Write all snippets as TOML
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Add snippets from a TOML file
+    Import {
+        file: std::path::PathBuf,
+        /// Replace snippets that already exist
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyAction {
+    /// Create a key; use the same passphrase or key file on all your machines
+    Init {
+        /// Derive the key from a passphrase (read from PRYNT_PASSPHRASE or prompted)
+        #[arg(long, conflicts_with = "keyfile")]
+        passphrase: bool,
+        /// The salt another machine printed for the same passphrase
+        #[arg(long, requires = "passphrase")]
+        salt: Option<String>,
+        /// Use the keys in this key file, e.g. one copied from another machine
+        #[arg(long)]
+        keyfile: Option<std::path::PathBuf>,
+        /// Replace an existing key file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Switch to a new key for new data, keeping old keys to read older data
+    Rotate {
+        /// Derive the new key from a passphrase
+        #[arg(long)]
+        passphrase: bool,
+        /// The salt another machine printed for the same passphrase
+        #[arg(long, requires = "passphrase")]
+        salt: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupAction {
+    /// Back up the history database now
+    Create,
+    /// List backups, newest first
+    List,
+    /// Replace the history database with a backup (the current one is backed up first)
+    Restore {
+        /// Number from `prynt backup list` (1 is the newest) or file name
+        backup: String,
+        /// Skip the confirmation prompt
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Encrypt the database and its backups, or switch to another way of unlocking them
+    Encrypt {
+        /// Where the key is kept
+        #[arg(long, value_enum, default_value = "keyfile")]
+        with: storage::Unlock,
+    },
+    /// Store the database and its backups unencrypted again
+    Decrypt,
+}
+
+#[derive(clap::Args)]
+struct AliasOptions {
+    /// Only consider commands used at least this often
+    #[arg(long, default_value_t = 3)]
+    min_count: usize,
+    /// Only alias commands and prefixes at least this many characters long
+    #[arg(long, default_value_t = 12)]
+    min_length: usize,
+    /// Seconds between commands that still count as running them back to back
+    #[arg(long, default_value_t = 120)]
+    within: i64,
+    /// Number of suggestions
+    #[arg(long, default_value_t = 10)]
+    limit: usize,
+}
+
+#[derive(Subcommand)]
+enum AliasAction {
+    /// List suggested aliases and functions with the keystrokes they would save
+    Suggest {
+        #[command(flatten)]
+        options: AliasOptions,
+    },
+    /// Print the suggestions as shell definitions
+    Export {
+        #[arg(long, value_enum)]
+        shell: shell::Shell,
+        #[command(flatten)]
+        options: AliasOptions,
+    },
+}
+
 fn main() {
+    let cli = Cli::parse();
     let db_path = dirs::home_dir().unwrap().join(".context/prynt.sqlite");
     let db_dir = db_path.parent().unwrap();
-    
-    // Create the .context directory if it doesn't exist
-    if !db_dir.exists() {
-        std::fs::create_dir_all(db_dir).expect("Failed to create .context directory");
-    }
-    
-    let db_path_str = db_path.to_str().unwrap();
-    let conn = init_db(db_path_str).expect("Failed to initialize database");
 
-    let cli = Cli::parse();
+    let config = config::load_config(&db_dir.join("config.toml"));
+    let key_path = config.encryption.keyfile.clone().unwrap_or_else(|| db_dir.join("prynt.key"));
+    let db_keyfile = config.database.keyfile.clone().unwrap_or_else(|| db_dir.join("prynt-db.key"));
+    // PRYNT_JSONL lets a CI job keep its history in a file of its own without writing a config
+    let history_file = std::env::var_os("PRYNT_JSONL").map(std::path::PathBuf::from).or_else(|| {
+        (config.database.backend == config::Backend::Jsonl).then(|| config.database.history_file.clone().unwrap_or_else(|| db_dir.join("prynt.jsonl")))
+    });
+    let (mut store, db_key): (Box<dyn Store>, Option<String>) = match history_file {
+        Some(path) => (Box::new(JsonlStore::open(path)), None),
+        None => {
+            // Create the .context directory if it doesn't exist
+            if !db_dir.exists() {
+                std::fs::create_dir_all(db_dir).expect("Failed to create .context directory");
+            }
+            // Only you may read your history, whatever the umask
+            storage::make_private(db_dir).expect("Failed to restrict access to the .context directory");
+            storage::prepare_private(&db_path).expect("Failed to restrict access to the database");
+            // The shell hook has no terminal to ask for a passphrase on
+            let interactive = !matches!(cli.command, Commands::LogCmd { .. });
+            let db_key = storage::is_encrypted(&db_path).then(|| unlock_database(&db_path, &db_keyfile, interactive));
+            let store = HistoryStore::open_with_key(&db_path, db_key.as_deref()).unwrap_or_else(|e| {
+                eprintln!("prynt: could not open {}: {}", db_path.display(), e);
+                std::process::exit(1);
+            });
+            (Box::new(store), db_key)
+        }
+    };
+
+    // Keep the shell hook fast: maintenance only runs on interactive commands
+    if let Some(conn) = store.sqlite()
+        && !matches!(cli.command, Commands::LogCmd { .. } | Commands::Prune { .. } | Commands::Backup { .. } | Commands::Serve { .. })
+        && retention::maintenance_due(conn, &config.retention).expect("Failed to read maintenance state")
+    {
+        if config.retention.keep_raw_days.is_some() {
+            snapshot(conn, db_dir, &config, "retention", db_key.as_deref());
+        }
+        let pruned = retention::run_maintenance(conn, &config.retention).expect("Failed to run database maintenance");
+        if pruned > 0 {
+            eprintln!("prynt: pruned {} command(s) past the retention period.", pruned);
+        }
+    }
 
     match cli.command {
-        Commands::LogCmd { command, cwd, exit_code, duration_secs } => {
-            logger::log_command(&conn, command, cwd, exit_code, duration_secs);
-        }
-        Commands::Log { reverse, less } => {
-            let order = if reverse { "DESC" } else { "ASC" };
-            let query = format!("SELECT id, timestamp, cwd, command, exit_code, duration_secs FROM command_logs ORDER BY timestamp {}", order);
-            let mut stmt = conn.prepare(&query).unwrap();
-            let logs = stmt.query_map([], |row| {
-                Ok(CommandLog {
-                    id: row.get(0)?,
-                    timestamp: DateTime::parse_from_rfc3339(&row.get::<_, String>(1)?).unwrap().with_timezone(&Local),
-                    cwd: row.get(2)?,
-                    command: row.get(3)?,
-                    exit_code: row.get(4)?,
-                    duration_secs: row.get(5)?,
-                })
-            }).unwrap();
+        Commands::LogCmd { command, cwd, exit_code, duration_secs, started_at_ns, duration_ns } => {
+            // Logging `prynt forget --match <secret>` would store the secret right back
+            if command.split_whitespace().take(2).eq(["prynt", "forget"]) {
+                return;
+            }
+            // Runs after every command, so a busy or broken database must not spill a panic into the shell
+            if let Err(e) = logger::log_command(&*store, command, cwd, exit_code, duration_secs, started_at_ns, duration_ns) {
+                eprintln!("prynt: could not log the command: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Log { reverse, less, tag } => {
+            let order = if reverse { Order::NewestFirst } else { Order::OldestFirst };
+            let logs = store.logs(&Filter { tag, ..Filter::default() }, order, None).unwrap();
             let mut output = String::new();
-            for log in logs {
-                let log = log.unwrap();
-                output.push_str(&format!("[{}] {}\n  Dir: {}\n  Exit: {} | Duration: {:.2}s\n\n", log.timestamp, log.command, log.cwd, log.exit_code, log.duration_secs));
+            for log in &logs {
+                output.push_str(&format_entry(&*store, log));
+                output.push('\n');
             }
             if less {
                 use std::process::{Command, Stdio};
@@ -124,257 +568,854 @@ fn main() {
                 print!("{}", output);
             }
         }
-        Commands::Today { export, markdown } => {
-            use chrono::Duration;
-            let now = Local::now();
-            let since = now - Duration::hours(24);
-            let mut stmt = conn.prepare("SELECT timestamp, cwd, command, duration_secs FROM command_logs WHERE timestamp >= ?1 ORDER BY timestamp ASC").unwrap();
-            let logs = stmt.query_map([since.to_rfc3339()], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, f64>(3)?))
-            }).unwrap();
-            let mut total_commands = 0;
-            let mut total_time = 0.0;
-            use std::collections::HashMap;
-            let mut folder_time: HashMap<String, f64> = HashMap::new();
-            let mut command_count: HashMap<String, usize> = HashMap::new();
-            let mut first_timestamp: Option<DateTime<Local>> = None;
-            let mut last_timestamp: Option<DateTime<Local>> = None;
-            for log in logs {
-                let (timestamp, cwd, command, duration): (String, String, String, f64) = log.unwrap();
-                let trimmed = command.trim_start();
-                if trimmed == "prynt" || trimmed.starts_with("prynt ") { continue; }
-                let ts = DateTime::parse_from_rfc3339(&timestamp).unwrap().with_timezone(&Local);
-                if first_timestamp.is_none() { first_timestamp = Some(ts); }
-                last_timestamp = Some(ts);
-                *folder_time.entry(cwd.clone()).or_insert(0.0) += duration;
-                *command_count.entry(command.clone()).or_insert(0) += 1;
-                total_commands += 1;
-                total_time += duration;
-            }
-            if export || markdown {
-                let mut folders: Vec<_> = folder_time.into_iter().collect();
-                folders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-                let top_folders: Vec<_> = folders.into_iter().take(3).collect();
-                let mut commands: Vec<_> = command_count.into_iter().collect();
-                commands.sort_by(|a, b| b.1.cmp(&a.1));
-                if markdown {
-                    println!("## Productivity Summary (Today)");
-                    println!("- **Total commands:** {}", total_commands);
-                    println!("- **Total terminal time:** {:.2} seconds", total_time);
-                    if let (Some(first), Some(last)) = (first_timestamp, last_timestamp) {
-                        let uptime = last.signed_duration_since(first).num_seconds();
-                        println!("- **Total terminal uptime:** {} seconds", uptime);
-                    } else {
-                        println!("- **Total terminal uptime:** N/A");
-                    }
-                    println!("- **Top 3 most worked folders:**");
-                    for (i, (folder, time)) in top_folders.iter().enumerate() {
-                        println!("  {}. {} (`{:.2}` seconds)", i + 1, folder, time);
-                    }
-                    println!("- **Top 3 most used commands:**");
-                    for (i, (cmd, count)) in commands.iter().take(3).enumerate() {
-                        println!("  {}. `{}` ({} times)", i + 1, cmd, count);
-                    }
+        Commands::Today { export, markdown, idle_gap, tag } => {
+            let range = TimeRange::last(Duration::hours(24), clock::now());
+            let idle_gap = idle_gap_duration(idle_gap.unwrap_or(config.sessions.idle_gap_minutes));
+            print_period(&*store, range, "Today", export, markdown, false, idle_gap, tag);
+        }
+        Commands::Weekly { export, markdown, idle_gap, tag } => {
+            let range = TimeRange::last(Duration::days(7), clock::now());
+            let idle_gap = idle_gap_duration(idle_gap.unwrap_or(config.sessions.idle_gap_minutes));
+            print_period(&*store, range, "Weekly", export, markdown, true, idle_gap, tag);
+        }
+        Commands::Timesheet { week, format, round, idle_gap, output } => {
+            let since = if week { clock::now() - Duration::days(7) } else { clock::now() - Duration::hours(24) };
+            let idle_gap = idle_gap_duration(idle_gap.unwrap_or(config.sessions.idle_gap_minutes));
+            let logs = store.query().since(since).exclude_prynt().fetch().unwrap();
+            let counted: Vec<_> = logs.iter().collect();
+            let groups = session::sessions_by_day_and_project(&counted, idle_gap);
+            let rendered = match format {
+                timesheet::TimesheetFormat::Csv => timesheet::render_csv(&groups, minutes_duration(round.unwrap_or(config.timesheet.round_minutes), "A rounding increment")),
+                timesheet::TimesheetFormat::Ics => timesheet::render_ics(&groups),
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, rendered).expect("Failed to write timesheet");
+                    println!("Timesheet written to {}", path.display());
+                }
+                None => print!("{}", rendered),
+            }
+        }
+        Commands::Summary { folder } => {
+            let totals = store.query().cwd_contains(folder.as_str()).totals().unwrap();
+            println!("Summary for '{}':\n  Commands run: {}\n  Total time spent: {:.2} seconds", folder, totals.runs, totals.duration_ns as f64 / 1e9);
+        }
+        Commands::Clear => {
+            use std::io::{self, Write};
+            print!("Are you sure you want to clear all logs? [y/N]: ");
+            io::stdout().flush().unwrap();
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer).unwrap();
+            if answer.trim().eq_ignore_ascii_case("y") {
+                if let Some(conn) = store.sqlite() {
+                    snapshot(conn, db_dir, &config, "clear", db_key.as_deref());
+                }
+                if let Err(e) = store.clear() {
+                    eprintln!("Could not clear the history: {}", e);
+                    std::process::exit(1);
+                }
+                if store.sqlite().is_some() {
+                    println!("All logs have been cleared. Undo with `prynt backup restore 1`.");
                 } else {
-                    println!("Productivity Summary (Today):");
-                    println!("Total commands: {}", total_commands);
-                    println!("Total terminal time: {:.2} seconds", total_time);
-                    if let (Some(first), Some(last)) = (first_timestamp, last_timestamp) {
-                        let uptime = last.signed_duration_since(first).num_seconds();
-                        println!("Total terminal uptime: {} seconds", uptime);
-                    } else {
-                        println!("Total terminal uptime: N/A");
-                    }
-                    println!("Top 3 most worked folders:");
-                    for (i, (folder, time)) in top_folders.iter().enumerate() {
-                        println!("  {}. {} ({:.2} seconds)", i + 1, folder, time);
-                    }
-                    println!("Top 3 most used commands:");
-                    for (i, (cmd, count)) in commands.iter().take(3).enumerate() {
-                        println!("  {}. {} ({} times)", i + 1, cmd, count);
-                    }
+                    println!("All logs have been cleared.");
                 }
             } else {
-                let mut stmt = conn.prepare("SELECT id, timestamp, cwd, command, exit_code, duration_secs FROM command_logs WHERE timestamp >= ?1 ORDER BY timestamp ASC").unwrap();
-                let logs = stmt.query_map([since.to_rfc3339()], |row| {
-                    Ok(CommandLog {
-                        id: row.get(0)?,
-                        timestamp: DateTime::parse_from_rfc3339(&row.get::<_, String>(1)?).unwrap().with_timezone(&Local),
-                        cwd: row.get(2)?,
-                        command: row.get(3)?,
-                        exit_code: row.get(4)?,
-                        duration_secs: row.get(5)?,
+                println!("Aborted. No logs were cleared.");
+            }
+        }
+        Commands::Forget { ids, last, pattern, since, interactive, yes } => {
+            let mut matching: Vec<CommandLog> = if last {
+                store.query().exclude_prynt().newest_first().first().unwrap().into_iter().collect()
+            } else if !ids.is_empty() {
+                ids.iter().filter_map(|id| store.get(&resolve_id(&*store, id)).unwrap()).collect()
+            } else {
+                let start = since.map(|age| {
+                    clock::now().checked_sub_signed(age).unwrap_or_else(|| {
+                        eprintln!("--since {} days is too far back.", age.num_days());
+                        std::process::exit(1);
                     })
-                }).unwrap();
-                for log in logs {
-                    let log = log.unwrap();
-                    println!("[{}] {}\n  Dir: {}\n  Exit: {} | Duration: {:.2}s\n", log.timestamp, log.command, log.cwd, log.exit_code, log.duration_secs);
-                }
-            }
-        }
-        Commands::Weekly { export, markdown } => {
-            use chrono::Duration;
-            let now = Local::now();
-            let since = now - Duration::days(7);
-            let mut stmt = conn.prepare("SELECT timestamp, cwd, command, duration_secs FROM command_logs WHERE timestamp >= ?1 ORDER BY timestamp ASC").unwrap();
-            let logs = stmt.query_map([since.to_rfc3339()], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, f64>(3)?))
-            }).unwrap();
-            let mut total_commands = 0;
-            let mut total_time = 0.0;
-            use std::collections::HashMap;
-            let mut folder_time: HashMap<String, f64> = HashMap::new();
-            let mut command_count: HashMap<String, usize> = HashMap::new();
-            let mut first_timestamp: Option<DateTime<Local>> = None;
-            let mut last_timestamp: Option<DateTime<Local>> = None;
-            for log in logs {
-                let (timestamp, cwd, command, duration): (String, String, String, f64) = log.unwrap();
-                let trimmed = command.trim_start();
-                if trimmed == "prynt" || trimmed.starts_with("prynt ") { continue; }
-                let ts = DateTime::parse_from_rfc3339(&timestamp).unwrap().with_timezone(&Local);
-                if first_timestamp.is_none() { first_timestamp = Some(ts); }
-                last_timestamp = Some(ts);
-                *folder_time.entry(cwd.clone()).or_insert(0.0) += duration;
-                *command_count.entry(command.clone()).or_insert(0) += 1;
-                total_commands += 1;
-                total_time += duration;
-            }
-            if export || markdown {
-                let mut folders: Vec<_> = folder_time.into_iter().collect();
-                folders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-                let top_folders: Vec<_> = folders.into_iter().take(3).collect();
-                let mut commands: Vec<_> = command_count.into_iter().collect();
-                commands.sort_by(|a, b| b.1.cmp(&a.1));
-                if markdown {
-                    println!("## Productivity Summary (Weekly)");
-                    println!("- **Total commands:** {}", total_commands);
-                    println!("- **Total terminal time:** {:.2} seconds", total_time);
-                    if let (Some(first), Some(last)) = (first_timestamp, last_timestamp) {
-                        let uptime = last.signed_duration_since(first).num_seconds();
-                        println!("- **Total terminal uptime:** {} seconds", uptime);
-                    } else {
-                        println!("- **Total terminal uptime:** N/A");
+                });
+                let logs = store.query().range(TimeRange { start, end: None }).fetch().unwrap();
+                logs.into_iter().filter(|log| pattern.as_ref().is_none_or(|re| re.is_match(&log.command))).collect()
+            };
+            if let Some(re) = &pattern {
+                matching.retain(|log| re.is_match(&log.command));
+            }
+            if matching.is_empty() {
+                println!("No commands match. Nothing was deleted.");
+                return;
+            }
+            if interactive {
+                for (i, log) in matching.iter().enumerate() {
+                    print!("{:>3}. ", i + 1);
+                    println!("{}", format_entry(&*store, log));
+                }
+                let Some(selected) = prompt::parse_selection(&prompt::ask("Entries to delete (e.g. 1 3-5, empty to skip): "), matching.len()) else {
+                    println!("Invalid selection. Nothing was deleted.");
+                    return;
+                };
+                if selected.is_empty() {
+                    return;
+                }
+                matching = matching.into_iter().enumerate().filter(|(i, _)| selected.contains(i)).map(|(_, log)| log).collect();
+            }
+            println!("{} command(s) will be permanently deleted:", matching.len());
+            for log in &matching {
+                println!("{}", format_entry(&*store, log));
+            }
+            if !yes && !prompt::confirm("Delete these commands?") {
+                println!("Aborted. Nothing was deleted.");
+                return;
+            }
+            // No new backup here, and existing ones lose the commands too: forgetting
+            // should leave no copy behind. Backups lose the same ids, and the pattern
+            // stands for everything to forget only when nothing else picked the commands
+            let by_pattern = ids.is_empty() && !last && !interactive && since.is_none();
+            let redact = |command: &str| by_pattern && pattern.as_ref().is_some_and(|re| re.is_match(command));
+            let ids: Vec<String> = matching.into_iter().map(|log| log.id).collect();
+            let deleted = store.delete(&ids).unwrap_or_else(|e| {
+                eprintln!("Could not delete the commands: {}", e);
+                std::process::exit(1);
+            });
+            if let Some(conn) = store.sqlite() {
+                retention::redact_rollups(conn, &redact).expect("Failed to redact daily totals");
+                // Rewrite the file so the deleted text does not linger in free pages
+                retention::compact(conn).expect("Failed to compact database");
+            }
+            println!("Deleted {} command(s).", deleted);
+            if store.sqlite().is_some() {
+                let backups = backup::forget(&db_dir.join("backups"), &ids, &redact, db_key.as_deref()).expect("Failed to read backups");
+                for (backup, outcome) in backups {
+                    match outcome {
+                        Ok(()) => println!("Removed them from backup {}.", backup.path.display()),
+                        Err(e) => eprintln!("Warning: {} may still contain them and could not be changed ({}). Delete it to be sure.", backup.path.display(), e),
                     }
-                    println!("- **Top 3 most worked folders:**");
-                    for (i, (folder, time)) in top_folders.iter().enumerate() {
-                        println!("  {}. {} (`{:.2}` seconds)", i + 1, folder, time);
+                }
+            }
+        }
+        Commands::Import { from, file } => {
+            let Some(path) = file.or_else(|| from.default_path()) else {
+                eprintln!("Could not find the default history location. Pass --file.");
+                std::process::exit(1);
+            };
+            let entries = match import::read(from, &path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("Could not read {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            };
+            // Untimed entries are dated before the file was last written when nothing earlier is known
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).map(DateTime::<Local>::from).unwrap_or_else(|_| clock::now());
+            let home = dirs::home_dir().unwrap().to_string_lossy().into_owned();
+            let report = import::import(&*store, from, &entries, modified, &home).expect("Failed to import history");
+            println!("Imported {} command(s) from {} ({} already in your history).", report.imported, path.display(), report.duplicates);
+        }
+        Commands::Export { to, project, since, until, output } => {
+            let under = project.map(|dir| dir.canonicalize().unwrap_or(dir).to_string_lossy().into_owned());
+            let logs = store.logs(&Filter { range: TimeRange { start: since, end: until }, under, ..Filter::default() }, Order::OldestFirst, None).unwrap();
+            let rendered = export::render(&logs, to);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, rendered).expect("Failed to write history file");
+                    println!("Exported {} command(s) to {}", logs.len(), path.display());
+                }
+                None => std::io::Write::write_all(&mut std::io::stdout(), &rendered).expect("Failed to write history"),
+            }
+        }
+        Commands::Dump { output } => {
+            let conn = require_sqlite(&*store);
+            let records = archive::records(conn).expect("Failed to read history");
+            let file = std::fs::File::create(&output).expect("Failed to create archive");
+            let keyring = load_keyring(&key_path);
+            if keyring.is_none() {
+                eprintln!("Warning: writing an unencrypted archive. Run `prynt key init` to encrypt dumps.");
+            }
+            archive::write(&records, file, keyring.as_ref()).expect("Failed to write archive");
+            let commands = records.iter().filter(|r| matches!(r, archive::Record::Command { .. })).count();
+            println!("Dumped {} command(s) and their tags, notes and snippets to {}", commands, output.display());
+        }
+        Commands::Restore { archive: path } => {
+            let conn = require_sqlite(&*store);
+            let file = std::fs::File::open(&path).unwrap_or_else(|e| {
+                eprintln!("Could not open {}: {}", path.display(), e);
+                std::process::exit(1);
+            });
+            let records = match archive::read(file, load_keyring(&key_path).as_ref()) {
+                Ok(records) => records,
+                Err(e) => {
+                    eprintln!("Could not read {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            };
+            snapshot(conn, db_dir, &config, "merge", db_key.as_deref());
+            let report = archive::merge(conn, &records).expect("Failed to merge archive");
+            println!(
+                "Added {} command(s), {} tag(s), {} note(s), {} snippet(s) and {} daily total(s); {} already present.",
+                report.commands, report.tags, report.notes, report.snippets, report.rollups, report.unchanged
+            );
+            if !report.conflicts.is_empty() {
+                println!("{} conflict(s), local versions kept:", report.conflicts.len());
+                for conflict in &report.conflicts {
+                    println!("  {} {}: {}", conflict.kind, conflict.key, conflict.detail);
+                }
+            }
+            for error in &report.errors {
+                eprintln!("Warning: {}.", error);
+            }
+        }
+        Commands::Sync { target } => {
+            let conn = require_sqlite(&*store);
+            let Some(target) = target.or(config.sync.target.clone()) else {
+                eprintln!("No sync target. Pass a folder or URL, or set sync.target in the config.");
+                std::process::exit(1);
+            };
+            let keyring = load_keyring(&key_path);
+            if keyring.is_none() {
+                eprintln!("Warning: syncing unencrypted history. Run `prynt key init` on each machine to encrypt it.");
+            }
+            match sync::sync(conn, &target, keyring.as_ref()) {
+                Ok(report) => {
+                    println!("Sent {} change(s) and received {} from {} machine(s) via {}.", report.sent, report.received, report.machines, target);
+                    println!(
+                        "Added {} command(s), {} tag(s), {} note(s) and {} snippet(s); removed {} forgotten command(s).",
+                        report.merge.commands, report.merge.tags, report.merge.notes, report.merge.snippets, report.merge.forgotten
+                    );
+                    for conflict in &report.merge.conflicts {
+                        println!("  conflict, kept local {} {}: {}", conflict.kind, conflict.key, conflict.detail);
                     }
-                    println!("- **Top 3 most used commands:**");
-                    for (i, (cmd, count)) in commands.iter().take(3).enumerate() {
-                        println!("  {}. `{}` ({} times)", i + 1, cmd, count);
+                    for error in &report.merge.errors {
+                        eprintln!("Warning: {}.", error);
                     }
-                } else {
-                    println!("Productivity Summary (Weekly):");
-                    println!("Total commands: {}", total_commands);
-                    println!("Total terminal time: {:.2} seconds", total_time);
-                    if let (Some(first), Some(last)) = (first_timestamp, last_timestamp) {
-                        let uptime = last.signed_duration_since(first).num_seconds();
-                        println!("Total terminal uptime: {} seconds", uptime);
-                    } else {
-                        println!("Total terminal uptime: N/A");
+                }
+                Err(e) => {
+                    eprintln!("Sync with {} failed: {}", target, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Serve { listen, dir, team } => {
+            let dir = dir.unwrap_or_else(|| db_dir.join("server"));
+            println!("Serving change logs from {} on http://{}", dir.display(), listen);
+            if team {
+                println!("Team dashboard: http://{}/team", listen);
+            }
+            if let Err(e) = serve::serve(&listen, &dir, team) {
+                eprintln!("Server failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Share { team_url, days, dry_run } => {
+            let Some(url) = team_url.or(config.team.url.clone()) else {
+                eprintln!("No team server. Pass --team-url or set team.url in the config.");
+                std::process::exit(1);
+            };
+            if team::first_day(days).is_none() {
+                eprintln!("--days {} is out of range.", days);
+                std::process::exit(1);
+            }
+            let url = url.trim_end_matches('/');
+            let member = team::member_id(require_sqlite(&*store), url).expect("Failed to read team member id");
+            let upload = team::upload(&*store, member, days).expect("Failed to read history");
+            if dry_run {
+                println!("{}", serde_json::to_string_pretty(&upload).unwrap());
+                return;
+            }
+            if let Err(e) = team::send(url, &upload) {
+                eprintln!("Could not share with {}: {}", url, e);
+                std::process::exit(1);
+            }
+            let programs: std::collections::HashSet<_> = upload.stats.iter().map(|stat| &stat.program).collect();
+            println!("Shared the last {} days of {} program(s) with {}: only program names with their run counts, failures and time.", days, programs.len(), url);
+        }
+        Commands::Key { action } => match action {
+            KeyAction::Init { passphrase, salt, keyfile, force } => {
+                if key_path.exists() && !force {
+                    eprintln!("{} already exists. Use `prynt key rotate` to change keys, or --force to replace it.", key_path.display());
+                    std::process::exit(1);
+                }
+                let keyring = match keyfile {
+                    Some(path) => match crypto::Keyring::load(&path) {
+                        Ok(Some(keyring)) if !keyring.keys.is_empty() => keyring,
+                        Ok(_) => {
+                            eprintln!("{} contains no keys.", path.display());
+                            std::process::exit(1);
+                        }
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    },
+                    None => {
+                        let mut keyring = crypto::Keyring::default();
+                        keyring.rotate(new_key(passphrase, salt.as_deref()));
+                        keyring
                     }
-                    println!("Top 3 most worked folders:");
-                    for (i, (folder, time)) in top_folders.iter().enumerate() {
-                        println!("  {}. {} ({:.2} seconds)", i + 1, folder, time);
+                };
+                keyring.save(&key_path).expect("Failed to write key file");
+                println!("Key {} saved to {}.", keyring.keys[0].id, key_path.display());
+                match &keyring.keys[0].salt {
+                    Some(salt) => println!("On your other machines, run `prynt key init --passphrase --salt {}` with the same passphrase.", salt),
+                    None => println!("Copy this file to your other machines and run `prynt key init --keyfile <file>` there."),
+                }
+            }
+            KeyAction::Rotate { passphrase, salt } => {
+                let Some(mut keyring) = load_keyring(&key_path) else {
+                    eprintln!("No key yet. Run `prynt key init` first.");
+                    std::process::exit(1);
+                };
+                let old = keyring.keys[0].id.clone();
+                keyring.rotate(new_key(passphrase, salt.as_deref()));
+                keyring.save(&key_path).expect("Failed to write key file");
+                println!("New data is now encrypted with key {} (was {}). Older keys are kept for reading.", keyring.keys[0].id, old);
+                match &keyring.keys[0].salt {
+                    Some(salt) => println!("Run `prynt key rotate --passphrase --salt {}` with the same passphrase on your other machines before they sync again.", salt),
+                    None => println!("Give your other machines the new key before they sync again."),
+                }
+            }
+        },
+        Commands::Backup { action } => {
+            let conn = require_sqlite(&*store);
+            let dir = db_dir.join("backups");
+            match action {
+                BackupAction::Create => {
+                    snapshot(conn, db_dir, &config, "manual", db_key.as_deref());
+                }
+                BackupAction::List => {
+                    let backups = backup::list(&dir).expect("Failed to list backups");
+                    if backups.is_empty() {
+                        println!("No backups yet.");
                     }
-                    println!("Top 3 most used commands:");
-                    for (i, (cmd, count)) in commands.iter().take(3).enumerate() {
-                        println!("  {}. {} ({} times)", i + 1, cmd, count);
+                    for (i, b) in backups.iter().enumerate() {
+                        println!("{:>3}. {} {} ({} KB)\n     {}", i + 1, b.created_at.format("%Y-%m-%d %H:%M:%S"), b.reason, b.size.div_ceil(1024), b.path.display());
                     }
                 }
-            } else {
-                let mut stmt = conn.prepare("SELECT id, timestamp, cwd, command, exit_code, duration_secs FROM command_logs WHERE timestamp >= ?1 ORDER BY timestamp DESC").unwrap();
-                let logs = stmt.query_map([since.to_rfc3339()], |row| {
-                    Ok(CommandLog {
-                        id: row.get(0)?,
-                        timestamp: DateTime::parse_from_rfc3339(&row.get::<_, String>(1)?).unwrap().with_timezone(&Local),
-                        cwd: row.get(2)?,
-                        command: row.get(3)?,
-                        exit_code: row.get(4)?,
-                        duration_secs: row.get(5)?,
-                    })
-                }).unwrap();
-                for log in logs {
-                    let log = log.unwrap();
-                    println!("[{}] {}\n  Dir: {}\n  Exit: {} | Duration: {:.2}s\n", log.timestamp, log.command, log.cwd, log.exit_code, log.duration_secs);
+                BackupAction::Restore { backup: wanted, yes } => {
+                    let backups = backup::list(&dir).expect("Failed to list backups");
+                    let chosen = match wanted.parse::<usize>() {
+                        Ok(n) if n >= 1 => backups.get(n - 1),
+                        _ => backups.iter().find(|b| b.path.file_name().is_some_and(|name| name.to_string_lossy() == wanted)),
+                    };
+                    let Some(chosen) = chosen else {
+                        eprintln!("No backup '{}'. See `prynt backup list`.", wanted);
+                        std::process::exit(1);
+                    };
+                    println!("Restore the {1} backup from {0}?", chosen.created_at.format("%Y-%m-%d %H:%M:%S"), chosen.reason);
+                    if !yes && !prompt::confirm("This replaces your current history") {
+                        println!("Aborted. Nothing was restored.");
+                        return;
+                    }
+                    let path = chosen.path.clone();
+                    snapshot(conn, db_dir, &config, "restore", db_key.as_deref());
+                    backup::restore(store.sqlite_mut().unwrap(), &path, db_key.as_deref()).expect("Failed to restore backup");
+                    println!("Restored {}.", path.display());
                 }
             }
         }
-        Commands::Summary { folder } => {
-            let mut stmt = conn.prepare("SELECT COUNT(*), SUM(duration_secs) FROM command_logs WHERE cwd LIKE ?1").unwrap();
-            let like_pattern = format!("%{}%", folder);
-            let mut rows = stmt.query([like_pattern]).unwrap();
-            if let Some(row) = rows.next().unwrap() {
-                let count: i64 = row.get(0).unwrap_or(0);
-                let total_time: f64 = row.get(1).unwrap_or(0.0);
-                println!("Summary for '{}':\n  Commands run: {}\n  Total time spent: {:.2} seconds", folder, count, total_time);
-            } else {
-                println!("No data found for project/folder '{}'.", folder);
+        Commands::Db { action } => {
+            let conn = require_sqlite(&*store);
+            if !storage::SUPPORTED {
+                eprintln!("This prynt was built without database encryption. Reinstall it with `cargo install prynt --features sqlcipher`.");
+                std::process::exit(1);
+            }
+            let unlock = match action {
+                DbAction::Encrypt { with } => Some(with),
+                DbAction::Decrypt => None,
+            };
+            if unlock == Some(storage::Unlock::Keyring) && !cfg!(feature = "keyring") {
+                eprintln!("This prynt was built without OS keyring support. Reinstall it with `--features sqlcipher,keyring`, or use --with keyfile.");
+                std::process::exit(1);
+            }
+            let new_key = unlock.map(|unlock| match unlock {
+                storage::Unlock::Passphrase => read_passphrase("PRYNT_DB_PASSPHRASE"),
+                _ => storage::random_key(),
+            });
+            snapshot(conn, db_dir, &config, if unlock.is_some() { "encrypt" } else { "decrypt" }, db_key.as_deref());
+            let backups: Vec<_> = backup::list(&db_dir.join("backups")).expect("Failed to list backups").into_iter().map(|b| b.path).collect();
+            let staged = match storage::stage(conn, &db_path, &backups, db_key.as_deref(), new_key.as_deref()) {
+                Ok(staged) => staged,
+                Err(e) => {
+                    eprintln!("Could not re-encrypt history ({}). Nothing was changed.", e);
+                    std::process::exit(1);
+                }
+            };
+            // The new key is stored before anything depends on it
+            match (unlock, &new_key) {
+                (Some(storage::Unlock::Keyfile), Some(key)) => storage::write_keyfile(&db_keyfile, key).expect("Failed to write key file"),
+                (Some(storage::Unlock::Keyring), Some(key)) => {
+                    if let Err(e) = storage::write_keyring(key) {
+                        storage::discard(&staged);
+                        eprintln!("Could not store the key in the OS keyring ({}). Nothing was changed.", e);
+                        std::process::exit(1);
+                    }
+                }
+                _ => {}
+            }
+            drop(store);
+            storage::replace(&staged).expect("Failed to replace the database");
+            // Keys that no longer unlock anything
+            if unlock != Some(storage::Unlock::Keyfile) && db_keyfile.exists() {
+                std::fs::remove_file(&db_keyfile).expect("Failed to remove old key file");
+            }
+            if unlock != Some(storage::Unlock::Keyring) {
+                storage::delete_keyring();
+            }
+            match unlock {
+                Some(storage::Unlock::Keyfile) => println!("History and {} backup(s) are encrypted; the key is in {}.", backups.len(), db_keyfile.display()),
+                Some(unlock) => println!("History and {} backup(s) are encrypted and unlocked with your {}.", backups.len(), unlock),
+                None => println!("History and {} backup(s) are no longer encrypted.", backups.len()),
+            }
+            if unlock == Some(storage::Unlock::Passphrase) {
+                println!("The shell hook cannot ask for it: export PRYNT_DB_PASSPHRASE in your shell session, or commands are not logged.");
             }
         }
-        Commands::Clear => {
-            use std::io::{self, Write};
-            print!("Are you sure you want to clear all logs? [y/N]: ");
-            io::stdout().flush().unwrap();
-            let mut answer = String::new();
-            io::stdin().read_line(&mut answer).unwrap();
-            if answer.trim().eq_ignore_ascii_case("y") {
-                conn.execute("DELETE FROM command_logs", []).expect("Failed to clear logs");
-                println!("All logs have been cleared.");
-            } else {
-                println!("Aborted. No logs were cleared.");
+        Commands::Prune { older_than, cwd, pattern, dry_run, yes } => {
+            let conn = require_sqlite(&*store);
+            // The config's day count was checked when it was loaded
+            let older_than = match older_than {
+                Some(age) => Some(clock::now().checked_sub_signed(age).unwrap_or_else(|| {
+                    eprintln!("--older-than {} days is too far back.", age.num_days());
+                    std::process::exit(1);
+                })),
+                None => config.retention.keep_raw_days.and_then(retention::days_ago),
+            };
+            if older_than.is_none() && cwd.is_none() && pattern.is_none() {
+                eprintln!("Nothing to prune by. Pass --older-than, --cwd or --match, or set retention.keep_raw_days in the config.");
+                std::process::exit(1);
+            }
+            let rollup = match (config.retention.keep_aggregates, &pattern) {
+                (false, _) => retention::Rollup::None,
+                (true, Some(_)) => retention::Rollup::Redacted,
+                (true, None) => retention::Rollup::Totals,
+            };
+            let filter = retention::PruneFilter {
+                older_than,
+                cwd_glob: cwd,
+                pattern,
+                keep_annotated: config.retention.keep_tagged,
+            };
+            let matching = retention::candidates(conn, &filter).unwrap();
+            if matching.is_empty() {
+                println!("No commands match. Nothing to prune.");
+                return;
+            }
+            println!("{} command(s) match:", matching.len());
+            for log in matching.iter().take(10) {
+                println!("  [{}] {} ({})", log.started_at.format("%Y-%m-%d %H:%M"), log.command, log.cwd);
+            }
+            if matching.len() > 10 {
+                println!("  ... and {} more", matching.len() - 10);
             }
+            if dry_run {
+                println!("Dry run. Nothing was deleted.");
+                return;
+            }
+            if !yes && !prompt::confirm("Delete these commands?") {
+                println!("Aborted. Nothing was deleted.");
+                return;
+            }
+            snapshot(conn, db_dir, &config, "prune", db_key.as_deref());
+            let ids: Vec<String> = matching.into_iter().map(|log| log.id).collect();
+            let deleted = retention::prune(conn, &ids, rollup).expect("Failed to prune logs");
+            retention::compact(conn).expect("Failed to compact database");
+            println!("Pruned {} command(s).", deleted);
         }
         Commands::Top { n } => {
-            let mut stmt = conn.prepare("SELECT command, COUNT(*) as cnt FROM command_logs WHERE command NOT LIKE 'prynt%' GROUP BY command ORDER BY cnt DESC LIMIT ?1").unwrap();
-            let rows = stmt.query_map([n as i64], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-            }).unwrap();
+            let top = store.top_commands(&Filter { exclude_prynt: true, ..Filter::default() }, n).unwrap();
             println!("Top {} most used commands:", n);
-            for (i, row) in rows.enumerate() {
-                let (cmd, count) = row.unwrap();
-                println!("  {}. {} ({} times)", i + 1, cmd, count);
+            for (i, entry) in top.iter().enumerate() {
+                println!("  {}. {} ({} times)", i + 1, entry.command, entry.runs);
             }
         }
         Commands::Projects => {
-            let mut stmt = conn.prepare("SELECT cwd, COUNT(*), SUM(duration_secs) FROM command_logs WHERE command NOT LIKE 'prynt%' GROUP BY cwd ORDER BY COUNT(*) DESC").unwrap();
-            let rows = stmt.query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?))
-            }).unwrap();
+            let folders = store.folders(&Filter { exclude_prynt: true, ..Filter::default() }).unwrap();
             println!("Project folders:");
-            for (i, row) in rows.enumerate() {
-                let (cwd, count, total_time) = row.unwrap();
-                println!("  {}. {} ({} commands, {:.2} seconds)", i + 1, cwd, count, total_time);
+            for (i, folder) in folders.iter().enumerate() {
+                println!("  {}. {} ({} commands, {:.2} seconds)", i + 1, folder.cwd, folder.runs, folder.duration_ns as f64 / 1e9);
             }
         }
-        Commands::Search { pattern } => {
-            let like_pattern = format!("%{}%", pattern);
-            let mut stmt = conn.prepare("SELECT timestamp, cwd, command, exit_code, duration_secs FROM command_logs WHERE command LIKE ?1 AND command NOT LIKE 'prynt%' ORDER BY timestamp ASC").unwrap();
-            let rows = stmt.query_map([like_pattern], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i32>(3)?, row.get::<_, f64>(4)?))
-            }).unwrap();
+        Commands::Search { pattern, tag, interactive } => {
+            let filter = Filter { command_contains: Some(pattern.clone()), tag, exclude_prynt: true, ..Filter::default() };
+            let logs = store.logs(&filter, Order::OldestFirst, None).unwrap();
             println!("Search results for '{}':", pattern);
-            for row in rows {
-                let (timestamp, cwd, command, exit_code, duration) = row.unwrap();
-                println!("[{}] {}\n  Dir: {}\n  Exit: {} | Duration: {:.2}s\n", timestamp, command, cwd, exit_code, duration);
+            for (i, log) in logs.iter().enumerate() {
+                if interactive {
+                    print!("{:>3}. ", i + 1);
+                }
+                println!("{}", format_entry(&*store, log));
+            }
+            if interactive && !logs.is_empty() {
+                let Some(selected) = prompt::parse_selection(&prompt::ask("Entries to tag (e.g. 1 3-5, empty to skip): "), logs.len()) else {
+                    println!("Invalid selection. Nothing was tagged.");
+                    return;
+                };
+                if selected.is_empty() {
+                    return;
+                }
+                let tags: Vec<String> = prompt::ask("Tags (space separated): ").split_whitespace().map(String::from).collect();
+                if tags.is_empty() {
+                    println!("No tags given. Nothing was tagged.");
+                    return;
+                }
+                for &i in &selected {
+                    db::add_tags(require_sqlite(&*store), &logs[i].id, &tags).expect("Failed to tag command");
+                }
+                println!("Tagged {} command(s) with {}.", selected.len(), tags.join(", "));
             }
         }
-        Commands::Stats => {
-            let mut stmt = conn.prepare("SELECT COUNT(*), SUM(duration_secs), MIN(duration_secs), MAX(duration_secs), AVG(duration_secs) FROM command_logs WHERE command NOT LIKE 'prynt%'").unwrap();
-            let mut rows = stmt.query([]).unwrap();
-            if let Some(row) = rows.next().unwrap() {
-                let total: i64 = row.get(0).unwrap_or(0);
-                let sum: f64 = row.get(1).unwrap_or(0.0);
-                let min: f64 = row.get(2).unwrap_or(0.0);
-                let max: f64 = row.get(3).unwrap_or(0.0);
-                let avg: f64 = row.get(4).unwrap_or(0.0);
-                println!("Overall Productivity Stats:");
-                println!("  Total commands: {}", total);
-                println!("  Total terminal time: {:.2} seconds", sum);
-                println!("  Shortest command: {:.2} seconds", min);
-                println!("  Longest command: {:.2} seconds", max);
-                println!("  Average command duration: {:.2} seconds", avg);
+        Commands::Tag { id, tags, remove } => {
+            let conn = require_sqlite(&*store);
+            let id = resolve_id(&*store, &id);
+            if remove {
+                db::remove_tags(conn, &id, &tags).expect("Failed to remove tags");
+                println!("Removed {} from {}.", tags.join(", "), short_id(&id));
+            } else {
+                db::add_tags(conn, &id, &tags).expect("Failed to tag command");
+                println!("Tagged {} with {}.", short_id(&id), tags.join(", "));
+            }
+        }
+        Commands::Note { id, text, clear } => {
+            let conn = require_sqlite(&*store);
+            let id = resolve_id(&*store, &id);
+            let note = if clear { None } else { text.as_deref() };
+            db::set_note(conn, &id, note).expect("Failed to save note");
+            if note.is_some() {
+                println!("Note saved on {}.", short_id(&id));
+            } else {
+                println!("Note removed from {}.", short_id(&id));
+            }
+        }
+        Commands::Rerun { target, dry_run, here, yes } => {
+            // A word like `add` or `cafe` is also a valid id prefix, so a matching
+            // command wins, and ids need as many characters as `log` shows
+            let by_id = || {
+                let mut ids = if target.len() >= SHORT_ID_LEN { store.ids_with_prefix(&target).unwrap() } else { Vec::new() };
+                if ids.len() == 1 { store.get(&ids.remove(0)).unwrap() } else { None }
+            };
+            let original = match store.query().command_contains(target.as_str()).exclude_prynt().newest_first().first().unwrap().or_else(by_id) {
+                Some(log) => log,
+                None => {
+                    eprintln!("No command matching '{}' or with id '{}' (ids need at least {} characters).", target, target, SHORT_ID_LEN);
+                    std::process::exit(1);
+                }
+            };
+            let cwd = if here { std::env::current_dir().expect("Failed to read current directory") } else { std::path::PathBuf::from(&original.cwd) };
+            println!("Command: {}\nDir: {}", original.command, cwd.display());
+            if dry_run {
+                return;
+            }
+            if !cwd.is_dir() {
+                eprintln!("Directory {} no longer exists. Use --here to run in the current directory.", cwd.display());
+                std::process::exit(1);
+            }
+            if !yes && !prompt::confirm("Run it?") {
+                println!("Aborted.");
+                return;
+            }
+            let result = runner::run_in_shell(&original.command, &cwd).expect("Failed to run command");
+            match logger::log_command(&*store, original.command.clone(), cwd.to_string_lossy().into_owned(), result.exit_code, 0.0, result.started_at.timestamp_nanos_opt(), Some(result.duration_ns)) {
+                Ok(id) => {
+                    if let Some(conn) = store.sqlite() {
+                        db::set_rerun_of(conn, &id, &original.id).expect("Failed to link rerun");
+                    }
+                }
+                Err(e) => eprintln!("prynt: could not log the command: {}", e),
+            }
+            std::process::exit(result.exit_code);
+        }
+        Commands::Suggest { cwd, last, prefix, limit, raw } => {
+            let cwd = cwd.unwrap_or_else(|| std::env::current_dir().expect("Failed to read current directory").to_string_lossy().into_owned());
+            let idle_gap = idle_gap_duration(config.sessions.idle_gap_minutes);
+            let now = clock::now();
+            let last = last.or_else(|| {
+                store.query().exclude_prynt().newest_first().first().unwrap()
+                    .filter(|log| now - log.ended_at <= idle_gap)
+                    .map(|log| log.command)
+            });
+            let logs = store.query().under(session::project_root(&cwd)).fetch().unwrap();
+            let suggestions: Vec<_> = suggest::rank(&logs, &cwd, now, last.as_deref(), idle_gap)
+                .into_iter()
+                .filter(|s| prefix.as_deref().is_none_or(|p| s.command.starts_with(p)))
+                .take(limit)
+                .collect();
+            if raw {
+                for suggestion in suggestions {
+                    println!("{}", suggestion.command);
+                }
+                return;
+            }
+            if suggestions.is_empty() {
+                println!("No suggestions for {} yet.", cwd);
+                return;
+            }
+            println!("Suggestions for {}:", cwd);
+            for (i, suggestion) in suggestions.iter().enumerate() {
+                println!("  {}. {} ({} runs, score {:.1})", i + 1, suggestion.command, suggestion.runs, suggestion.score);
+            }
+        }
+        Commands::Jump { keywords, interactive, list } => {
+            let current = std::env::current_dir().ok().map(|dir| dir.to_string_lossy().into_owned());
+            let visits: Vec<_> = store.query().fetch().unwrap().into_iter().map(|log| (log.cwd, log.started_at)).collect();
+            let candidates = jump::rank(&visits, &keywords, clock::now(), current.as_deref());
+            if candidates.is_empty() {
+                eprintln!("No directory matches '{}'.", keywords.join(" "));
+                std::process::exit(1);
+            }
+            if list {
+                for candidate in candidates.iter().take(10) {
+                    println!("{:>8.2}  {}", candidate.score, candidate.dir);
+                }
+            } else if interactive {
+                // The shell function captures stdout, so the menu goes to stderr
+                let shown = &candidates[..candidates.len().min(10)];
+                for (i, candidate) in shown.iter().enumerate() {
+                    eprintln!("{:>3}. {}", i + 1, candidate.dir);
+                }
+                eprint!("Jump to: ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer).unwrap();
+                match answer.trim().parse::<usize>() {
+                    Ok(n) if (1..=shown.len()).contains(&n) => println!("{}", shown[n - 1].dir),
+                    _ => std::process::exit(1),
+                }
+            } else {
+                println!("{}", candidates[0].dir);
+            }
+        }
+        Commands::Failures { days, within, limit } => {
+            let since = match days {
+                Some(days) => Duration::try_days(days).and_then(|days| clock::now().checked_sub_signed(days)).unwrap_or_else(|| {
+                    eprintln!("--days {} is too far back.", days);
+                    std::process::exit(1);
+                }),
+                None => DateTime::UNIX_EPOCH.with_timezone(&Local),
+            };
+            let Some(within) = Duration::try_seconds(within) else {
+                eprintln!("--within {} seconds is too long.", within);
+                std::process::exit(1);
+            };
+            let logs = store.query().since(since).exclude_prynt().fetch().unwrap();
+            let counted: Vec<_> = logs.iter().collect();
+            let report = failures::analyze(&counted, within, limit);
+            println!("Failure rate by command:");
+            for (program, rate) in &report.by_program {
+                println!("  {} — {:.0}% ({} of {} runs)", program, rate.rate() * 100.0, rate.failures, rate.runs);
+            }
+            println!("Failure rate by project:");
+            for (project, rate) in &report.by_project {
+                println!("  {} — {:.0}% ({} of {} runs)", project, rate.rate() * 100.0, rate.failures, rate.runs);
+            }
+            println!("Most frequent failing commands:");
+            for (i, (command, count)) in report.top_failing.iter().enumerate() {
+                println!("  {}. {} ({} failures)", i + 1, command, count);
+            }
+            println!("Fixed by (failure followed by a successful edit within {}s):", within.num_seconds());
+            for fix in &report.fixed_by {
+                println!("  {}  →  {} ({} times)", fix.failed, fix.fixed, fix.count);
+            }
+            println!("Flaky commands (alternating pass/fail in the same directory):");
+            for flaky in &report.flaky {
+                println!("  {}\n    Dir: {} | {} runs, {} failures, {} flips", flaky.command, flaky.cwd, flaky.runs, flaky.failures, flaky.flips);
+            }
+        }
+        Commands::Typos { within, aliases, min_count } => {
+            let Some(within) = Duration::try_seconds(within) else {
+                eprintln!("--within {} seconds is too long.", within);
+                std::process::exit(1);
+            };
+            let logs = store.query().fetch().unwrap();
+            let counted: Vec<_> = logs.iter().filter(|log| !log.is_prynt()).collect();
+            let report = typos::analyze(&counted, within);
+            if let Some(shell) = aliases {
+                print!("{}", typos::render_aliases(&report.typos, shell, min_count));
+                return;
+            }
+            println!("Most common typos:");
+            for (i, typo) in report.typos.iter().enumerate() {
+                let kind = if typo.position == 0 { "command" } else { "argument" };
+                println!("  {}. {} → {} ({} times, {})", i + 1, typo.typo, typo.fix, typo.count, kind);
+            }
+            println!("Commands not found:");
+            for (program, count) in &report.not_found {
+                println!("  {} ({} times)", program, count);
+            }
+        }
+        Commands::Aliases { action } => {
+            let (options, shell) = match action {
+                AliasAction::Suggest { options } => (options, None),
+                AliasAction::Export { shell, options } => (options, Some(shell)),
+            };
+            let Some(within) = Duration::try_seconds(options.within) else {
+                eprintln!("--within {} seconds is too long.", options.within);
+                std::process::exit(1);
+            };
+            let logs = store.query().fetch().unwrap();
+            let counted: Vec<_> = logs.iter().filter(|log| !log.is_prynt()).collect();
+            let mut suggestions = aliases::suggest(&counted, options.min_count, options.min_length, within);
+            suggestions.truncate(options.limit);
+            if let Some(shell) = shell {
+                print!("{}", aliases::render(&suggestions, shell));
+                return;
+            }
+            if suggestions.is_empty() {
+                println!("No repetitive commands found yet.");
+                return;
+            }
+            println!("Alias and function suggestions:");
+            for (i, suggestion) in suggestions.iter().enumerate() {
+                let kind = match suggestion.kind {
+                    aliases::Kind::Command => "alias",
+                    aliases::Kind::Prefix => "alias, takes arguments",
+                    aliases::Kind::Sequence(_) => "function",
+                };
+                println!("  {}. {} = {}\n     {} uses, saves ~{} keystrokes ({})", i + 1, suggestion.name, suggestion.expansion, suggestion.uses, suggestion.keystrokes_saved, kind);
+            }
+        }
+        Commands::Workflows { min_count, max_len, idle_gap, limit, save, output } => {
+            let logs = store.query().fetch().unwrap();
+            let counted: Vec<_> = logs.iter().filter(|log| !log.is_prynt()).collect();
+            let idle_gap = idle_gap_duration(idle_gap.unwrap_or(config.sessions.idle_gap_minutes));
+            let mut found = workflows::mine(&counted, idle_gap, max_len.max(2), min_count);
+            found.truncate(limit);
+            if let Some(n) = save {
+                let Some(workflow) = n.checked_sub(1).and_then(|i| found.get(i)) else {
+                    eprintln!("No workflow number {}. Run 'prynt workflows' to see the list.", n);
+                    std::process::exit(1);
+                };
+                let path = output.unwrap_or_else(|| std::path::PathBuf::from(format!("workflow-{}.sh", n)));
+                std::fs::write(&path, workflows::render_script(workflow)).expect("Failed to write script");
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).expect("Failed to make script executable");
+                }
+                println!("Saved workflow {} to {}", n, path.display());
+                return;
+            }
+            if found.is_empty() {
+                println!("No recurring command sequences found yet.");
+                return;
             }
+            println!("Recurring workflows:");
+            for (i, workflow) in found.iter().enumerate() {
+                println!("  {}. {}\n     {} times, typically takes {}", i + 1, workflow.commands.join(" → "), workflow.count, session::format_duration(workflow.typical_duration));
+            }
+            println!("\nSave one as a script with: prynt workflows --save <n> [--output <file>]");
+        }
+        Commands::Save { id, name, command, description, force } => {
+            let conn = require_sqlite(&*store);
+            let id = resolve_id(&*store, &id);
+            let command = command.unwrap_or_else(|| store.get(&id).unwrap().unwrap().command);
+            let snippet = snippets::Snippet { name, command, description };
+            if snippets::save_snippet(conn, &snippet, Some(&id), force).expect("Failed to save snippet") {
+                println!("Saved snippet '{}': {}", snippet.name, snippet.command);
+            } else {
+                println!("A snippet named '{}' already exists. Use --force to replace it.", snippet.name);
+            }
+        }
+        Commands::Snippets { action } => {
+            let conn = require_sqlite(&*store);
+            match action {
+                SnippetAction::List => {
+                    let all = snippets::list_snippets(conn).unwrap();
+                    if all.is_empty() {
+                        println!("No snippets saved yet. Use 'prynt save <id> --name <name>' to add one.");
+                    }
+                    for snippet in all {
+                        match snippet.description {
+                            Some(description) => println!("{} — {}\n  {}", snippet.name, description, snippet.command),
+                            None => println!("{}\n  {}", snippet.name, snippet.command),
+                        }
+                    }
+                }
+                SnippetAction::Show { name } => {
+                    let snippet = get_snippet_or_exit(conn, &name);
+                    println!("Name: {}", snippet.name);
+                    if let Some(description) = &snippet.description {
+                        println!("Description: {}", description);
+                    }
+                    println!("Command: {}", snippet.command);
+                    let names = snippets::placeholders(&snippet.command);
+                    if !names.is_empty() {
+                        println!("Placeholders: {}", names.join(", "));
+                    }
+                }
+                SnippetAction::Run { name, values, dry_run } => {
+                    let snippet = get_snippet_or_exit(conn, &name);
+                    let mut filled: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+                    for value in values {
+                        let Some((key, value)) = value.split_once('=') else {
+                            eprintln!("Invalid --set '{}', expected key=value.", value);
+                            std::process::exit(1);
+                        };
+                        filled.insert(key.to_string(), value.to_string());
+                    }
+                    for placeholder in snippets::placeholders(&snippet.command) {
+                        if let std::collections::hash_map::Entry::Vacant(entry) = filled.entry(placeholder) {
+                            let value = prompt::ask(&format!("{}: ", entry.key()));
+                            entry.insert(value);
+                        }
+                    }
+                    let command = snippets::fill(&snippet.command, &filled);
+                    println!("$ {}", command);
+                    if !dry_run {
+                        let cwd = std::env::current_dir().expect("Failed to read current directory");
+                        let result = runner::run_in_shell(&command, &cwd).expect("Failed to run snippet");
+                        if let Err(e) = logger::log_command(&*store, command, cwd.to_string_lossy().into_owned(), result.exit_code, 0.0, result.started_at.timestamp_nanos_opt(), Some(result.duration_ns)) {
+                            eprintln!("prynt: could not log the command: {}", e);
+                        }
+                        std::process::exit(result.exit_code);
+                    }
+                }
+                SnippetAction::Rm { name } => {
+                    if snippets::remove_snippet(conn, &name).expect("Failed to remove snippet") {
+                        println!("Removed snippet '{}'.", name);
+                    } else {
+                        println!("No snippet named '{}'.", name);
+                    }
+                }
+                SnippetAction::Export { output } => {
+                    let collection = snippets::SnippetCollection { snippets: snippets::list_snippets(conn).unwrap() };
+                    let rendered = toml::to_string_pretty(&collection).expect("Failed to serialize snippets");
+                    match output {
+                        Some(path) => {
+                            std::fs::write(&path, rendered).expect("Failed to write snippets");
+                            println!("Exported {} snippet(s) to {}", collection.snippets.len(), path.display());
+                        }
+                        None => print!("{}", rendered),
+                    }
+                }
+                SnippetAction::Import { file, overwrite } => {
+                    let contents = std::fs::read_to_string(&file).expect("Failed to read snippet file");
+                    let collection: snippets::SnippetCollection = match toml::from_str(&contents) {
+                        Ok(collection) => collection,
+                        Err(e) => {
+                            eprintln!("Invalid snippet file {}: {}", file.display(), e);
+                            std::process::exit(1);
+                        }
+                    };
+                    let mut imported = 0;
+                    for snippet in &collection.snippets {
+                        if snippets::save_snippet(conn, snippet, None, overwrite).expect("Failed to save snippet") {
+                            imported += 1;
+                        }
+                    }
+                    println!("Imported {} of {} snippet(s).", imported, collection.snippets.len());
+                    if imported < collection.snippets.len() {
+                        println!("Existing snippets were kept. Use --overwrite to replace them.");
+                    }
+                }
+            }
+        }
+        Commands::Stats => {
+            let totals = store.query().exclude_prynt().totals().unwrap();
+            let secs = |ns: Option<i64>| ns.unwrap_or(0) as f64 / 1e9;
+            println!("Overall Productivity Stats:");
+            println!("  Total commands: {}", totals.runs);
+            println!("  Total terminal time: {:.2} seconds", secs(Some(totals.duration_ns)));
+            println!("  Shortest command: {:.2} seconds", secs(totals.min_duration_ns));
+            println!("  Longest command: {:.2} seconds", secs(totals.max_duration_ns));
+            println!("  Average command duration: {:.2} seconds", secs(totals.average_ns()));
         }
         Commands::Init => {
             use std::env;
@@ -419,26 +1460,29 @@ fn main() {
                 }
                 shell
             }
+            // BSD date has no %N, so pad seconds to nanoseconds to keep the hook's units uniform
             #[cfg(target_os = "macos")]
-            let time_cmd = "date +%s";
+            let time_cmd = "date +%s000000000";
             #[cfg(not(target_os = "macos"))]
             let time_cmd = "date +%s%N";
             let shell = get_shell();
-            let mut snippet = String::new();
-            let mut config_path = String::new();
+            // Frecency directory jumping for bash and zsh
+            let posix_jump = "j() { local dir; dir=\"$(prynt jump -- \"$@\")\" && cd \"$dir\"; }\nji() { local dir; dir=\"$(prynt jump --interactive -- \"$@\")\" && cd \"$dir\"; }\n";
+            let snippet;
+            let config_path;
             if shell.contains("zsh") {
-                snippet = format!("function prynt_preexec() {{\n    export PRYNT_CMD_START_TIME=$({})\n    export PRYNT_CMD_TO_LOG=\"$1\"\n}}\nfunction prynt_precmd() {{\n    if [[ -n \"$PRYNT_CMD_START_TIME\" && -n \"$PRYNT_CMD_TO_LOG\" ]]; then\n        local end_time=$({})\n        local duration_ns=$((end_time - PRYNT_CMD_START_TIME))\n        local duration_s=$(awk \"BEGIN {{print $duration_ns/1000000000}}\")\n        local exit_code=$?\n        if [[ ! \"$PRYNT_CMD_TO_LOG\" =~ ^prynt($|[[:space:]]) ]]; then\n            prynt log-cmd \"$PRYNT_CMD_TO_LOG\" \"$PWD\" \"$exit_code\" \"$duration_s\"\n        fi\n        unset PRYNT_CMD_START_TIME\n        unset PRYNT_CMD_TO_LOG\n    fi\n}}\nautoload -Uz add-zsh-hook\nadd-zsh-hook preexec prynt_preexec\nadd-zsh-hook precmd prynt_precmd\n", time_cmd, time_cmd);
+                snippet = format!("function prynt_preexec() {{\n    export PRYNT_CMD_START_TIME=$({})\n    export PRYNT_CMD_TO_LOG=\"$1\"\n}}\nfunction prynt_precmd() {{\n    local exit_code=$?\n    if [[ -n \"$PRYNT_CMD_START_TIME\" && -n \"$PRYNT_CMD_TO_LOG\" ]]; then\n        local end_time=$({})\n        local duration_ns=$((end_time - PRYNT_CMD_START_TIME))\n        local duration_s=$(awk \"BEGIN {{print $duration_ns/1000000000}}\")\n        if [[ ! \"$PRYNT_CMD_TO_LOG\" =~ ^prynt($|[[:space:]]) ]]; then\n            prynt log-cmd \"$PRYNT_CMD_TO_LOG\" \"$PWD\" \"$exit_code\" \"$duration_s\" --started-at-ns \"$PRYNT_CMD_START_TIME\" --duration-ns \"$duration_ns\"\n        fi\n        unset PRYNT_CMD_START_TIME\n        unset PRYNT_CMD_TO_LOG\n    fi\n}}\nautoload -Uz add-zsh-hook\nadd-zsh-hook preexec prynt_preexec\nadd-zsh-hook precmd prynt_precmd\n\n# Suggestion strategy for zsh-autosuggestions: ZSH_AUTOSUGGEST_STRATEGY=(prynt history)\n_zsh_autosuggest_strategy_prynt() {{\n    typeset -g suggestion=\"$(prynt suggest --raw --limit 1 --prefix \"$1\" 2>/dev/null)\"\n}}\n\n{}", time_cmd, time_cmd, posix_jump);
                 config_path = format!("{}/.zshrc", env::var("HOME").unwrap());
             } else if shell.contains("fish") {
-                snippet = format!("function prynt_preexec --on-event fish_preexec\n    set -g PRYNT_CMD_START_TIME ({} )\n    set -g PRYNT_CMD_TO_LOG $argv[1]\nend\n\nfunction prynt_precmd --on-event fish_prompt\n    if test -n \"$PRYNT_CMD_START_TIME\" -a -n \"$PRYNT_CMD_TO_LOG\"\n        set end_time ({} )\n        set duration_ns (math $end_time - $PRYNT_CMD_START_TIME)\n        set duration_s (math --scale 2 $duration_ns / 1000000000)\n        set exit_code $status\n        if not string match -r '^prynt($|\\s)' -- $PRYNT_CMD_TO_LOG\n            prynt log-cmd \"$PRYNT_CMD_TO_LOG\" \"$PWD\" \"$exit_code\" \"$duration_s\"\n        end\n        set -e PRYNT_CMD_START_TIME\n        set -e PRYNT_CMD_TO_LOG\n    end\nend\n", time_cmd, time_cmd);
+                snippet = format!("function prynt_preexec --on-event fish_preexec\n    set -g PRYNT_CMD_START_TIME ({} )\n    set -g PRYNT_CMD_TO_LOG $argv[1]\nend\n\nfunction prynt_precmd --on-event fish_prompt\n    set -l exit_code $status\n    if test -n \"$PRYNT_CMD_START_TIME\" -a -n \"$PRYNT_CMD_TO_LOG\"\n        set end_time ({} )\n        set duration_ns (math $end_time - $PRYNT_CMD_START_TIME)\n        set duration_s (math --scale 2 $duration_ns / 1000000000)\n        if not string match -r '^prynt($|\\s)' -- $PRYNT_CMD_TO_LOG\n            prynt log-cmd \"$PRYNT_CMD_TO_LOG\" \"$PWD\" \"$exit_code\" \"$duration_s\" --started-at-ns \"$PRYNT_CMD_START_TIME\" --duration-ns \"$duration_ns\"\n        end\n        set -e PRYNT_CMD_START_TIME\n        set -e PRYNT_CMD_TO_LOG\n    end\nend\n\nfunction j\n    set -l dir (prynt jump -- $argv); and cd $dir\nend\nfunction ji\n    set -l dir (prynt jump --interactive -- $argv); and cd $dir\nend\n", time_cmd, time_cmd);
                 config_path = format!("{}/.config/fish/config.fish", env::var("HOME").unwrap());
             } else {
-                snippet = format!("[[ -f ~/.bash-preexec.sh ]] && source ~/.bash-preexec.sh\n\nfunction prynt_preexec() {{\n    export PRYNT_CMD_START_TIME=$({})\n    export PRYNT_CMD_TO_LOG=\"$1\"\n}}\nfunction prynt_precmd() {{\n    if [ -n \"$PRYNT_CMD_START_TIME\" ] && [ -n \"$PRYNT_CMD_TO_LOG\" ]; then\n        local end_time=$({})\n        local duration_ns=$((end_time - PRYNT_CMD_START_TIME))\n        local duration_s=$(awk \"BEGIN {{print $duration_ns/1000000000}}\")\n        local exit_code=$?\n        if [[ ! \"$PRYNT_CMD_TO_LOG\" =~ ^prynt($|[[:space:]]) ]]; then\n            prynt log-cmd \"$PRYNT_CMD_TO_LOG\" \"$PWD\" \"$exit_code\" \"$duration_s\"\n        fi\n        unset PRYNT_CMD_START_TIME\n        unset PRYNT_CMD_TO_LOG\n    fi\n}}\npreexec_functions+=(prynt_preexec)\nprecmd_functions+=(prynt_precmd)\n", time_cmd, time_cmd);
+                snippet = format!("[[ -f ~/.bash-preexec.sh ]] && source ~/.bash-preexec.sh\n\nfunction prynt_preexec() {{\n    export PRYNT_CMD_START_TIME=$({})\n    export PRYNT_CMD_TO_LOG=\"$1\"\n}}\nfunction prynt_precmd() {{\n    local exit_code=$?\n    if [ -n \"$PRYNT_CMD_START_TIME\" ] && [ -n \"$PRYNT_CMD_TO_LOG\" ]; then\n        local end_time=$({})\n        local duration_ns=$((end_time - PRYNT_CMD_START_TIME))\n        local duration_s=$(awk \"BEGIN {{print $duration_ns/1000000000}}\")\n        if [[ ! \"$PRYNT_CMD_TO_LOG\" =~ ^prynt($|[[:space:]]) ]]; then\n            prynt log-cmd \"$PRYNT_CMD_TO_LOG\" \"$PWD\" \"$exit_code\" \"$duration_s\" --started-at-ns \"$PRYNT_CMD_START_TIME\" --duration-ns \"$duration_ns\"\n        fi\n        unset PRYNT_CMD_START_TIME\n        unset PRYNT_CMD_TO_LOG\n    fi\n}}\npreexec_functions+=(prynt_preexec)\nprecmd_functions+=(prynt_precmd)\n\n{}", time_cmd, time_cmd, posix_jump);
                 config_path = format!("{}/.bashrc", env::var("HOME").unwrap());
             }
             println!("# The following snippet will enable prynt logging for your shell:\n\n{}", snippet);
             #[cfg(target_os = "macos")]
-            println!("\n**Note for macOS users:** For nanosecond precision, install GNU coreutils and use 'gdate' instead of 'date'.\nE.g., replace 'date +%s' with 'gdate +%s%N' in the snippet above after installing coreutils with 'brew install coreutils'.");
+            println!("\n**Note for macOS users:** For nanosecond precision, install GNU coreutils and use 'gdate' instead of 'date'.\nE.g., replace 'date +%s000000000' with 'gdate +%s%N' in the snippet above after installing coreutils with 'brew install coreutils'.");
             print!("\nWould you like to append this to {}? [y/N]: ", config_path);
             io::stdout().flush().unwrap();
             let mut answer = String::new();
@@ -455,3 +1499,178 @@ fn main() {
         }
     }
 }
+
+/// An idle gap given in minutes, from a flag or the config. Exits with an
+/// error when it is too long to represent.
+fn idle_gap_duration(minutes: u64) -> Duration {
+    minutes_duration(minutes, "An idle gap")
+}
+
+/// `minutes` as a duration, or exits saying that `what` is too long.
+fn minutes_duration(minutes: u64, what: &str) -> Duration {
+    i64::try_from(minutes).ok().and_then(Duration::try_minutes).unwrap_or_else(|| {
+        eprintln!("{} of {} minutes is too long.", what, minutes);
+        std::process::exit(1);
+    })
+}
+
+/// Prints the commands started in `range`, either as a plain list or as a
+/// productivity summary. Shared by `today` and `weekly`.
+#[allow(clippy::too_many_arguments)]
+fn print_period(store: &dyn Store, range: TimeRange, label: &str, export: bool, markdown: bool, newest_first: bool, idle_gap: Duration, tag: Option<String>) {
+    let mut logs = store.logs(&Filter { range, tag, ..Filter::default() }, Order::OldestFirst, None).unwrap();
+    if !(export || markdown) {
+        if newest_first {
+            logs.reverse();
+        }
+        for log in logs {
+            println!("{}", format_entry(store, &log));
+        }
+        return;
+    }
+    let report = PeriodReport::build(label, &logs, idle_gap);
+    print!("{}", if markdown { report.render_markdown() } else { report.render_text() });
+}
+
+/// The key of the encrypted database at `path`, from the key file, the OS
+/// keyring or PRYNT_DB_PASSPHRASE, or asked for when `interactive`.
+fn unlock_database(path: &std::path::Path, keyfile: &std::path::Path, interactive: bool) -> String {
+    if !storage::SUPPORTED {
+        eprintln!("prynt: {} is encrypted, but this prynt was built without the `sqlcipher` feature.", path.display());
+        std::process::exit(1);
+    }
+    let key = storage::read_keyfile(keyfile)
+        .filter(|key| storage::check(path, key))
+        .or_else(|| storage::read_keyring().filter(|key| storage::check(path, key)))
+        .or_else(|| std::env::var("PRYNT_DB_PASSPHRASE").ok().filter(|key| storage::check(path, key)));
+    if let Some(key) = key {
+        return key;
+    }
+    if interactive && atty::is(atty::Stream::Stdin) {
+        let passphrase = rpassword::prompt_password("Database passphrase: ").expect("Failed to read passphrase");
+        if storage::check(path, &passphrase) {
+            return passphrase;
+        }
+        eprintln!("Wrong passphrase.");
+    } else {
+        eprintln!("prynt: {} is encrypted and no key unlocks it. Set PRYNT_DB_PASSPHRASE or check {}.", path.display(), keyfile.display());
+    }
+    std::process::exit(1);
+}
+
+/// The key file at `path`, exiting with a message if it cannot be read.
+fn load_keyring(path: &std::path::Path) -> Option<crypto::Keyring> {
+    match crypto::Keyring::load(path) {
+        Ok(keyring) => keyring.filter(|k| !k.keys.is_empty()),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// A random key, or one derived from a passphrase and `salt` (a new random
+/// salt when `None`).
+fn new_key(passphrase: bool, salt: Option<&str>) -> crypto::Key {
+    if !passphrase {
+        return crypto::random_key();
+    }
+    crypto::passphrase_key(&read_passphrase("PRYNT_PASSPHRASE"), salt).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// A new passphrase from the environment variable `var` for scripts, otherwise
+/// asked for twice without echo.
+fn read_passphrase(var: &str) -> String {
+    if let Ok(passphrase) = std::env::var(var) {
+        return passphrase;
+    }
+    let passphrase = rpassword::prompt_password("Passphrase: ").expect("Failed to read passphrase");
+    if passphrase.len() < 12 {
+        eprintln!("Use a passphrase of at least 12 characters.");
+        std::process::exit(1);
+    }
+    if rpassword::prompt_password("Repeat passphrase: ").expect("Failed to read passphrase") != passphrase {
+        eprintln!("Passphrases do not match.");
+        std::process::exit(1);
+    }
+    passphrase
+}
+
+/// Backs up the database before a destructive operation and drops backups
+/// beyond the configured number, exiting if the backup cannot be made.
+fn snapshot(conn: &Connection, db_dir: &std::path::Path, config: &config::Config, reason: &str, db_key: Option<&str>) {
+    let dir = db_dir.join("backups");
+    std::fs::create_dir_all(&dir).expect("Failed to create backups directory");
+    storage::make_private(&dir).expect("Failed to restrict access to the backups directory");
+    match backup::create(conn, &dir, reason, db_key) {
+        Ok(path) => eprintln!("Backed up history to {}", path.display()),
+        Err(e) => {
+            eprintln!("Could not back up history ({}). Nothing was changed.", e);
+            std::process::exit(1);
+        }
+    }
+    backup::rotate(&dir, config.backup.keep.max(1)).expect("Failed to remove old backups");
+}
+
+/// The SQLite database behind `store`, exiting with a message for commands
+/// that need more than the commands themselves when history is kept elsewhere.
+fn require_sqlite(store: &dyn Store) -> &Connection {
+    store.sqlite().unwrap_or_else(|| {
+        eprintln!("This command needs the SQLite history database. Unset PRYNT_JSONL and database.backend to use it.");
+        std::process::exit(1);
+    })
+}
+
+/// How many characters of an id `log` and the other listings show.
+const SHORT_ID_LEN: usize = 8;
+
+/// The first 8 characters of an id, which is what listings show.
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(SHORT_ID_LEN)]
+}
+
+/// Expands an id prefix to the full id, exiting with a message if it matches
+/// no command or more than one.
+fn resolve_id(store: &dyn Store, prefix: &str) -> String {
+    let mut ids = store.ids_with_prefix(prefix).unwrap();
+    match ids.len() {
+        1 => ids.remove(0),
+        0 => {
+            eprintln!("No command with id '{}'.", prefix);
+            std::process::exit(1);
+        }
+        _ => {
+            eprintln!("Id '{}' is ambiguous, use more characters.", prefix);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Formats one history entry the way `log`, `today`, `weekly` and `search` list them.
+fn format_entry(store: &dyn Store, log: &CommandLog) -> String {
+    let mut entry = format!("[{}] {}\n  Dir: {}\n  Exit: {} | Duration: {:.2}s | Id: {}\n", log.started_at, log.command, log.cwd, log.exit_code, log.duration_secs(), short_id(&log.id));
+    let annotations = store.annotations(&log.id).unwrap();
+    if !annotations.tags.is_empty() {
+        entry.push_str(&format!("  Tags: {}\n", annotations.tags.join(", ")));
+    }
+    if let Some(note) = annotations.note {
+        entry.push_str(&format!("  Note: {}\n", note));
+    }
+    if let Some(original) = annotations.rerun_of {
+        entry.push_str(&format!("  Rerun of: {}\n", short_id(&original)));
+    }
+    entry
+}
+
+fn get_snippet_or_exit(conn: &Connection, name: &str) -> snippets::Snippet {
+    match snippets::get_snippet(conn, name).unwrap() {
+        Some(snippet) => snippet,
+        None => {
+            eprintln!("No snippet named '{}'.", name);
+            std::process::exit(1);
+        }
+    }
+}
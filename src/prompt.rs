@@ -0,0 +1,37 @@
+use std::io::{self, Write};
+
+/// Prints `question` and reads one line from stdin, trimmed.
+pub fn ask(question: &str) -> String {
+    print!("{}", question);
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    answer.trim().to_string()
+}
+
+/// Asks a y/N question. Anything but `y` counts as no.
+pub fn confirm(question: &str) -> bool {
+    ask(&format!("{} [y/N]: ", question)).eq_ignore_ascii_case("y")
+}
+
+/// Parses a selection like `1 3-5,8` into zero-based indices below `count`.
+/// Returns `None` if any part is not a valid number or range.
+pub fn parse_selection(input: &str, count: usize) -> Option<Vec<usize>> {
+    let mut selected = Vec::new();
+    for part in input.split([',', ' ']).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (a.parse::<usize>().ok()?, b.parse::<usize>().ok()?),
+            None => {
+                let n = part.parse::<usize>().ok()?;
+                (n, n)
+            }
+        };
+        if start == 0 || end < start || end > count {
+            return None;
+        }
+        selected.extend(start - 1..end);
+    }
+    selected.sort_unstable();
+    selected.dedup();
+    Some(selected)
+}
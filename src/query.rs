@@ -0,0 +1,80 @@
+use crate::db::CommandLog;
+use chrono::{DateTime, Duration, Local};
+
+/// A span of time, open at either end. The start is inclusive, the end exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeRange {
+    pub start: Option<DateTime<Local>>,
+    pub end: Option<DateTime<Local>>,
+}
+
+impl TimeRange {
+    /// All of time.
+    pub fn all() -> Self {
+        TimeRange::default()
+    }
+
+    pub fn since(start: DateTime<Local>) -> Self {
+        TimeRange { start: Some(start), end: None }
+    }
+
+    pub fn until(end: DateTime<Local>) -> Self {
+        TimeRange { start: None, end: Some(end) }
+    }
+
+    pub fn between(start: DateTime<Local>, end: DateTime<Local>) -> Self {
+        TimeRange { start: Some(start), end: Some(end) }
+    }
+
+    /// The `duration` before `now`, open-ended so commands still running at
+    /// `now` count: `prynt today` is `last(Duration::hours(24), now)`.
+    pub fn last(duration: Duration, now: DateTime<Local>) -> Self {
+        TimeRange::since(now - duration)
+    }
+
+    pub fn contains(&self, time: DateTime<Local>) -> bool {
+        self.start.is_none_or(|start| time >= start) && self.end.is_none_or(|end| time < end)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Order {
+    #[default]
+    OldestFirst,
+    NewestFirst,
+}
+
+/// Which commands a query returns. Every criterion that is set must match.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// When the command started
+    pub range: TimeRange,
+    /// Only commands carrying this tag
+    pub tag: Option<String>,
+    /// Substring of the command, ignoring ASCII case
+    pub command_contains: Option<String>,
+    /// Substring of the working directory, ignoring ASCII case
+    pub cwd_contains: Option<String>,
+    /// Only commands run in this directory or below it
+    pub under: Option<String>,
+    /// Leave out `prynt` invocations, as reports do
+    pub exclude_prynt: bool,
+}
+
+impl Filter {
+    /// Whether `log` matches everything but the tag, which needs the store.
+    pub fn matches(&self, log: &CommandLog) -> bool {
+        let contains = |text: &str, part: &Option<String>| part.as_ref().is_none_or(|part| text.to_ascii_lowercase().contains(&part.to_ascii_lowercase()));
+        self.range.contains(log.started_at)
+            && contains(&log.command, &self.command_contains)
+            && contains(&log.cwd, &self.cwd_contains)
+            && self.under.as_ref().is_none_or(|dir| is_under(&log.cwd, dir))
+            && !(self.exclude_prynt && log.is_prynt())
+    }
+}
+
+/// Whether `cwd` is `dir` or a directory below it.
+fn is_under(cwd: &str, dir: &str) -> bool {
+    let root = dir.trim_end_matches('/');
+    cwd == root || cwd.strip_prefix(root).is_some_and(|rest| rest.starts_with('/'))
+}
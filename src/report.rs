@@ -0,0 +1,154 @@
+use crate::db::CommandLog;
+use crate::session::{self, format_duration, ActivityStats};
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+
+/// How many folders and commands the summary lists.
+const TOP: usize = 3;
+
+/// The productivity summary of a period, as `prynt today` and `prynt weekly`
+/// print it. `prynt` invocations are left out.
+pub struct PeriodReport {
+    /// Name of the period in headings, e.g. `Today`
+    pub label: String,
+    pub total_commands: usize,
+    /// Time spent in commands, in seconds
+    pub total_secs: f64,
+    /// From the first command's start to the last one's end
+    pub uptime: Option<Duration>,
+    pub activity: ActivityStats,
+    pub by_day: BTreeMap<NaiveDate, ActivityStats>,
+    pub by_project: Vec<(String, ActivityStats)>,
+    /// Folders with the most time in commands, with that time in seconds
+    pub top_folders: Vec<(String, f64)>,
+    pub top_commands: Vec<(String, usize)>,
+}
+
+impl PeriodReport {
+    /// Summarizes `logs`, splitting work sessions at pauses longer than `idle_gap`.
+    pub fn build(label: &str, logs: &[CommandLog], idle_gap: Duration) -> Self {
+        let counted: Vec<&CommandLog> = logs.iter().filter(|log| !log.is_prynt()).collect();
+        let mut folder_time: HashMap<&str, f64> = HashMap::new();
+        let mut command_count: HashMap<&str, usize> = HashMap::new();
+        let mut first_start: Option<DateTime<Local>> = None;
+        let mut last_end: Option<DateTime<Local>> = None;
+        for log in &counted {
+            first_start = Some(first_start.map_or(log.started_at, |t| t.min(log.started_at)));
+            last_end = Some(last_end.map_or(log.ended_at, |t| t.max(log.ended_at)));
+            *folder_time.entry(&log.cwd).or_insert(0.0) += log.duration_secs();
+            *command_count.entry(&log.command).or_insert(0) += 1;
+        }
+        // Ties are broken by name so the same history always gives the same report
+        let mut top_folders: Vec<(String, f64)> = folder_time.into_iter().map(|(folder, time)| (folder.to_string(), time)).collect();
+        top_folders.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_folders.truncate(TOP);
+        let mut top_commands: Vec<(String, usize)> = command_count.into_iter().map(|(command, count)| (command.to_string(), count)).collect();
+        top_commands.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_commands.truncate(TOP);
+        PeriodReport {
+            label: label.to_string(),
+            total_commands: counted.len(),
+            // Not `sum()`, which is -0.0 for no commands and prints as -0.00
+            total_secs: counted.iter().fold(0.0, |total, log| total + log.duration_secs()),
+            uptime: first_start.zip(last_end).map(|(first, last)| last - first),
+            activity: ActivityStats::from_sessions(&session::sessionize(counted.iter().copied(), idle_gap)),
+            by_day: session::activity_by_day(&counted, idle_gap),
+            by_project: session::activity_by_project(&counted, idle_gap),
+            top_folders,
+            top_commands,
+        }
+    }
+
+    fn uptime_text(&self) -> String {
+        match self.uptime {
+            Some(uptime) => format!("{} seconds", uptime.num_seconds()),
+            None => "N/A".to_string(),
+        }
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!("Productivity Summary ({}):", self.label));
+        lines.push(format!("Total commands: {}", self.total_commands));
+        lines.push(format!("Total terminal time: {:.2} seconds", self.total_secs));
+        lines.push(format!("Total terminal uptime: {}", self.uptime_text()));
+        lines.push(format!("Active time: {}", format_duration(self.activity.active)));
+        lines.push(format!("Idle time: {}", format_duration(self.activity.idle)));
+        lines.push(format!("Work sessions: {}", self.activity.sessions));
+        lines.push(format!("Longest focus streak: {}", format_duration(self.activity.longest_streak)));
+        lines.push("Active time by day:".to_string());
+        for (day, stats) in &self.by_day {
+            lines.push(format!(
+                "  {}: {} active, {} idle, {} sessions, longest streak {}",
+                day,
+                format_duration(stats.active),
+                format_duration(stats.idle),
+                stats.sessions,
+                format_duration(stats.longest_streak)
+            ));
+        }
+        lines.push("Active time by project:".to_string());
+        for (project, stats) in &self.by_project {
+            lines.push(format!(
+                "  {}: {} active, {} idle, {} sessions, longest streak {}",
+                project,
+                format_duration(stats.active),
+                format_duration(stats.idle),
+                stats.sessions,
+                format_duration(stats.longest_streak)
+            ));
+        }
+        lines.push(format!("Top {} most worked folders:", TOP));
+        for (i, (folder, time)) in self.top_folders.iter().enumerate() {
+            lines.push(format!("  {}. {} ({:.2} seconds)", i + 1, folder, time));
+        }
+        lines.push(format!("Top {} most used commands:", TOP));
+        for (i, (command, count)) in self.top_commands.iter().enumerate() {
+            lines.push(format!("  {}. {} ({} times)", i + 1, command, count));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    pub fn render_markdown(&self) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!("## Productivity Summary ({})", self.label));
+        lines.push(format!("- **Total commands:** {}", self.total_commands));
+        lines.push(format!("- **Total terminal time:** {:.2} seconds", self.total_secs));
+        lines.push(format!("- **Total terminal uptime:** {}", self.uptime_text()));
+        lines.push(format!("- **Active time:** {}", format_duration(self.activity.active)));
+        lines.push(format!("- **Idle time:** {}", format_duration(self.activity.idle)));
+        lines.push(format!("- **Work sessions:** {}", self.activity.sessions));
+        lines.push(format!("- **Longest focus streak:** {}", format_duration(self.activity.longest_streak)));
+        lines.push("- **Active time by day:**".to_string());
+        for (day, stats) in &self.by_day {
+            lines.push(format!(
+                "  - {}: {} active, {} idle, {} sessions, longest streak {}",
+                day,
+                format_duration(stats.active),
+                format_duration(stats.idle),
+                stats.sessions,
+                format_duration(stats.longest_streak)
+            ));
+        }
+        lines.push("- **Active time by project:**".to_string());
+        for (project, stats) in &self.by_project {
+            lines.push(format!(
+                "  - {}: {} active, {} idle, {} sessions, longest streak {}",
+                project,
+                format_duration(stats.active),
+                format_duration(stats.idle),
+                stats.sessions,
+                format_duration(stats.longest_streak)
+            ));
+        }
+        lines.push(format!("- **Top {} most worked folders:**", TOP));
+        for (i, (folder, time)) in self.top_folders.iter().enumerate() {
+            lines.push(format!("  {}. {} (`{:.2}` seconds)", i + 1, folder, time));
+        }
+        lines.push(format!("- **Top {} most used commands:**", TOP));
+        for (i, (command, count)) in self.top_commands.iter().enumerate() {
+            lines.push(format!("  {}. `{}` ({} times)", i + 1, command, count));
+        }
+        lines.join("\n") + "\n"
+    }
+}
@@ -0,0 +1,158 @@
+use crate::config::RetentionConfig;
+use crate::db::{self, format_ts, log_from_row, CommandLog, LOG_COLUMNS};
+use chrono::{DateTime, Duration, Local};
+use regex::Regex;
+use rusqlite::{params, Connection, Result};
+
+/// Which rows `prune` removes. Every criterion that is set must match.
+pub struct PruneFilter {
+    pub older_than: Option<DateTime<Local>>,
+    /// SQLite GLOB pattern for the directory, e.g. `/tmp/*`
+    pub cwd_glob: Option<String>,
+    pub pattern: Option<Regex>,
+    /// Leave commands that have tags or a note alone
+    pub keep_annotated: bool,
+}
+
+/// The commands matching `filter`, oldest first.
+pub fn candidates(conn: &Connection, filter: &PruneFilter) -> Result<Vec<CommandLog>> {
+    let annotated = if filter.keep_annotated {
+        "AND id NOT IN (SELECT log_id FROM command_tags) AND id NOT IN (SELECT log_id FROM command_notes)"
+    } else {
+        ""
+    };
+    let query = format!(
+        "SELECT {} FROM command_logs WHERE (?1 IS NULL OR started_at < ?1) AND (?2 IS NULL OR cwd GLOB ?2) {} ORDER BY started_at ASC",
+        LOG_COLUMNS, annotated
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let older_than = filter.older_than.as_ref().map(format_ts);
+    let logs = stmt.query_map(params![older_than, filter.cwd_glob], log_from_row)?;
+    let mut matching = Vec::new();
+    for log in logs {
+        let log = log?;
+        if filter.pattern.as_ref().is_none_or(|re| re.is_match(&log.command)) {
+            matching.push(log);
+        }
+    }
+    Ok(matching)
+}
+
+/// What `prune` keeps of the commands it deletes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rollup {
+    /// Nothing
+    None,
+    /// Their runs, failures and durations per local day, directory and
+    /// command in `daily_rollups`, so all-time reports keep including them
+    Totals,
+    /// The same totals under `REDACTED` instead of the command, for commands
+    /// removed by pattern, which is how secrets are usually removed
+    Redacted,
+}
+
+/// The command text `Rollup::Redacted` keeps.
+pub const REDACTED: &str = "<redacted>";
+
+/// Adds a row inserted into `daily_rollups` to the totals already there.
+const MERGE_ROLLUP: &str = "ON CONFLICT (day, cwd, command) DO UPDATE SET
+    runs = runs + excluded.runs,
+    failures = failures + excluded.failures,
+    duration_ns = duration_ns + excluded.duration_ns,
+    min_duration_ns = min(min_duration_ns, excluded.min_duration_ns),
+    max_duration_ns = max(max_duration_ns, excluded.max_duration_ns)";
+
+/// Deletes the given commands, first adding them to the daily totals as
+/// `rollup` says.
+pub fn prune(conn: &Connection, ids: &[String], rollup: Rollup) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("CREATE TEMP TABLE IF NOT EXISTS prune_ids (id TEXT PRIMARY KEY)", [])?;
+    tx.execute("DELETE FROM prune_ids", [])?;
+    for id in ids {
+        tx.execute("INSERT OR IGNORE INTO prune_ids (id) VALUES (?1)", [id])?;
+    }
+    if rollup != Rollup::None {
+        let mut select = tx.prepare(&format!("SELECT {} FROM command_logs WHERE id IN (SELECT id FROM prune_ids)", LOG_COLUMNS))?;
+        let logs = select.query_map([], log_from_row)?.collect::<Result<Vec<_>>>()?;
+        let mut upsert = tx.prepare(&format!(
+            "INSERT INTO daily_rollups (day, cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns)
+             VALUES (?1, ?2, ?3, 1, ?4, ?5, ?5, ?5) {}",
+            MERGE_ROLLUP
+        ))?;
+        for log in &logs {
+            // Reports go by local days, while started_at is stored in UTC
+            let day = log.started_at.date_naive().to_string();
+            let command = if rollup == Rollup::Redacted { REDACTED } else { log.command.as_str() };
+            upsert.execute(params![day, log.cwd, command, (log.exit_code != 0) as i64, log.duration_ns])?;
+        }
+    }
+    let deleted = tx.execute("DELETE FROM command_logs WHERE id IN (SELECT id FROM prune_ids)", [])?;
+    tx.execute("DELETE FROM prune_ids", [])?;
+    tx.commit()?;
+    Ok(deleted)
+}
+
+/// Moves the daily totals of every command `matches` picks to `REDACTED`, so
+/// forgotten text does not live on in totals of earlier prunes. Returns how
+/// many rows were redacted.
+pub fn redact_rollups(conn: &Connection, matches: &dyn Fn(&str) -> bool) -> Result<usize> {
+    let commands: Vec<String> = conn
+        .prepare("SELECT DISTINCT command FROM daily_rollups WHERE command <> ?1")?
+        .query_map([REDACTED], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    let tx = conn.unchecked_transaction()?;
+    let mut redacted = 0;
+    for command in commands.iter().filter(|command| matches(command)) {
+        tx.execute(
+            &format!(
+                "INSERT INTO daily_rollups (day, cwd, command, runs, failures, duration_ns, min_duration_ns, max_duration_ns)
+                 SELECT day, cwd, ?2, runs, failures, duration_ns, min_duration_ns, max_duration_ns FROM daily_rollups WHERE command = ?1 {}",
+                MERGE_ROLLUP
+            ),
+            params![command, REDACTED],
+        )?;
+        redacted += tx.execute("DELETE FROM daily_rollups WHERE command = ?1", [command])?;
+    }
+    tx.commit()?;
+    Ok(redacted)
+}
+
+/// Reclaims space and refreshes the query planner's statistics.
+pub fn compact(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ANALYZE; VACUUM;")
+}
+
+/// The time `days` days before now, or `None` if that is before the earliest
+/// time that can be represented.
+pub fn days_ago(days: u64) -> Option<DateTime<Local>> {
+    i64::try_from(days).ok().and_then(Duration::try_days).and_then(|age| crate::clock::now().checked_sub_signed(age))
+}
+
+/// Whether `maintenance_interval_days` have passed since `run_maintenance` last ran.
+pub fn maintenance_due(conn: &Connection, config: &RetentionConfig) -> Result<bool> {
+    Ok(match db::get_meta(conn, "last_maintenance")? {
+        Some(last) => days_ago(config.maintenance_interval_days).is_some_and(|due| db::parse_ts(&last) <= due),
+        None => true,
+    })
+}
+
+/// Applies the configured retention rules and compacts the database. Returns
+/// how many rows were pruned.
+pub fn run_maintenance(conn: &Connection, config: &RetentionConfig) -> Result<usize> {
+    let now = crate::clock::now();
+    let mut pruned = 0;
+    // An unrepresentable age prunes nothing rather than everything
+    if let Some(cutoff) = config.keep_raw_days.and_then(days_ago) {
+        let filter = PruneFilter {
+            older_than: Some(cutoff),
+            cwd_glob: None,
+            pattern: None,
+            keep_annotated: config.keep_tagged,
+        };
+        let ids: Vec<String> = candidates(conn, &filter)?.into_iter().map(|log| log.id).collect();
+        pruned = prune(conn, &ids, if config.keep_aggregates { Rollup::Totals } else { Rollup::None })?;
+    }
+    compact(conn)?;
+    db::set_meta(conn, "last_maintenance", &format_ts(&now))?;
+    Ok(pruned)
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Local};
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+/// Outcome of a command run by prynt itself, ready to be logged.
+pub struct RunResult {
+    pub started_at: DateTime<Local>,
+    pub duration_ns: i64,
+    pub exit_code: i32,
+}
+
+/// Runs `command` through the user's shell (`$SHELL`, falling back to `sh`)
+/// in `cwd`, inheriting stdin/stdout/stderr.
+pub fn run_in_shell(command: &str, cwd: &Path) -> std::io::Result<RunResult> {
+    let shell = std::env::var("SHELL").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "sh".to_string());
+    let started_at = crate::clock::now();
+    let timer = Instant::now();
+    let status = Command::new(shell).arg("-c").arg(command).current_dir(cwd).status()?;
+    Ok(RunResult {
+        started_at,
+        duration_ns: timer.elapsed().as_nanos() as i64,
+        exit_code: status.code().unwrap_or(-1),
+    })
+}
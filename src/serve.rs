@@ -0,0 +1,85 @@
+use crate::{sync, team};
+use rusqlite::Connection;
+use std::error::Error;
+use std::path::Path;
+use tiny_http::{Method, Request, Response, Server};
+
+/// Serves the sync folder `dir` over HTTP until the process is stopped:
+/// `GET /sync` lists change logs with their line counts, `GET /sync/<host>?from=n`
+/// returns a log from line n, and `POST /sync/<host>?from=n` appends to it.
+/// With `team`, `POST /team` also takes uploads from `prynt share` into
+/// `team.sqlite` in `dir`, and `GET /team?days=n&min_runs=n` shows the team
+/// dashboard (`&format=json` for the totals per program).
+pub fn serve(listen: &str, dir: &Path, team: bool) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let mut team_db = if team {
+        let conn = Connection::open(dir.join("team.sqlite"))?;
+        team::init_server_db(&conn)?;
+        Some(conn)
+    } else {
+        None
+    };
+    let server = Server::http(listen).map_err(|e| e.to_string())?;
+    for mut request in server.incoming_requests() {
+        let (status, body) = handle(&mut request, dir, team_db.as_mut());
+        let _ = request.respond(Response::from_string(body).with_status_code(status));
+    }
+    Ok(())
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+fn handle(request: &mut Request, dir: &Path, team_db: Option<&mut Connection>) -> (u16, String) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let from: usize = query_param(query, "from").and_then(|n| n.parse().ok()).unwrap_or(0);
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let result = match (request.method(), segments.as_slice()) {
+        (Method::Get, ["sync"]) => sync::folder_hosts(dir).map(|hosts| serde_json::to_string(&hosts).unwrap()).map_err(|e| e.to_string()),
+        (Method::Get, ["sync", host]) if sync::is_host_id(host) => {
+            sync::folder_read(dir, host, from).map(|lines| lines.iter().map(|line| format!("{}\n", line)).collect()).map_err(|e| e.to_string())
+        }
+        (Method::Post, ["sync", host]) if sync::is_host_id(host) => {
+            let mut body = String::new();
+            if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                return (400, e.to_string());
+            }
+            let lines: Vec<String> = body.lines().map(String::from).collect();
+            // A wrong line count means two machines share a host id
+            return match sync::folder_append(dir, host, from, &lines) {
+                Ok(()) => (200, String::new()),
+                Err(e) => (409, e.to_string()),
+            };
+        }
+        (Method::Post, ["team"]) if let Some(conn) = team_db => {
+            let upload: team::Upload = match serde_json::from_reader(request.as_reader()) {
+                Ok(upload) => upload,
+                Err(e) => return (400, e.to_string()),
+            };
+            if let Err(e) = team::validate(&upload) {
+                return (400, e);
+            }
+            team::store(conn, &upload).map(|()| String::new()).map_err(|e| e.to_string())
+        }
+        (Method::Get, ["team"]) if let Some(conn) = team_db => {
+            let days = query_param(query, "days").and_then(|n| n.parse().ok()).unwrap_or(30);
+            if team::first_day(days).is_none() {
+                return (400, format!("days={} is out of range", days));
+            }
+            let body = if query_param(query, "format") == Some("json") {
+                team::totals(conn, days).map(|totals| serde_json::to_string(&totals).unwrap())
+            } else {
+                let min_runs = query_param(query, "min_runs").and_then(|n| n.parse().ok()).unwrap_or(20);
+                team::dashboard(conn, days, 10, min_runs)
+            };
+            body.map_err(|e| e.to_string())
+        }
+        _ => return (404, "not found".to_string()),
+    };
+    match result {
+        Ok(body) => (200, body),
+        Err(e) => (500, e.to_string()),
+    }
+}
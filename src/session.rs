@@ -0,0 +1,141 @@
+use crate::db::CommandLog;
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// A run of commands with no pause longer than the idle gap between them.
+pub struct WorkSession {
+    pub started_at: DateTime<Local>,
+    pub ended_at: DateTime<Local>,
+    pub commands: usize,
+}
+
+impl WorkSession {
+    pub fn duration(&self) -> Duration {
+        self.ended_at - self.started_at
+    }
+}
+
+/// Active/idle breakdown for a set of work sessions.
+#[derive(Default)]
+pub struct ActivityStats {
+    pub active: Duration,
+    pub idle: Duration,
+    pub sessions: usize,
+    pub longest_streak: Duration,
+}
+
+impl ActivityStats {
+    pub fn from_sessions(sessions: &[WorkSession]) -> Self {
+        let (Some(first), Some(last)) = (sessions.first(), sessions.last()) else {
+            return ActivityStats::default();
+        };
+        let active = sessions.iter().map(WorkSession::duration).fold(Duration::zero(), |a, b| a + b);
+        ActivityStats {
+            active,
+            idle: (last.ended_at - first.started_at) - active,
+            sessions: sessions.len(),
+            longest_streak: sessions.iter().map(WorkSession::duration).max().unwrap_or_default(),
+        }
+    }
+}
+
+/// Groups commands into work sessions. A new session starts whenever a command
+/// begins more than `idle_gap` after everything before it has finished.
+pub fn sessionize<'a>(logs: impl IntoIterator<Item = &'a CommandLog>, idle_gap: Duration) -> Vec<WorkSession> {
+    split_sessions(logs, idle_gap)
+        .into_iter()
+        .map(|logs| WorkSession {
+            started_at: logs[0].started_at,
+            ended_at: logs.iter().map(|log| log.ended_at).max().unwrap(),
+            commands: logs.len(),
+        })
+        .collect()
+}
+
+/// The commands of each work session, oldest first. See `sessionize`.
+pub fn split_sessions<'a>(logs: impl IntoIterator<Item = &'a CommandLog>, idle_gap: Duration) -> Vec<Vec<&'a CommandLog>> {
+    let mut logs: Vec<&CommandLog> = logs.into_iter().collect();
+    logs.sort_by_key(|log| log.started_at);
+    let mut sessions: Vec<Vec<&CommandLog>> = Vec::new();
+    let mut session_end: Option<DateTime<Local>> = None;
+    for log in logs {
+        match (sessions.last_mut(), session_end) {
+            (Some(session), Some(end)) if log.started_at - end <= idle_gap => {
+                session.push(log);
+                session_end = Some(end.max(log.ended_at));
+            }
+            _ => {
+                sessions.push(vec![log]);
+                session_end = Some(log.ended_at);
+            }
+        }
+    }
+    sessions
+}
+
+/// Activity per local calendar day. Sessions that cross midnight are split.
+pub fn activity_by_day(logs: &[&CommandLog], idle_gap: Duration) -> BTreeMap<NaiveDate, ActivityStats> {
+    let mut days: BTreeMap<NaiveDate, Vec<&CommandLog>> = BTreeMap::new();
+    for log in logs {
+        days.entry(log.started_at.date_naive()).or_default().push(log);
+    }
+    days.into_iter()
+        .map(|(day, logs)| (day, ActivityStats::from_sessions(&sessionize(logs, idle_gap))))
+        .collect()
+}
+
+/// Activity per project, most active first. Each project is sessionized on its
+/// own, so switching between two projects counts towards both.
+pub fn activity_by_project(logs: &[&CommandLog], idle_gap: Duration) -> Vec<(String, ActivityStats)> {
+    let mut projects: Vec<_> = group_by_project(logs)
+        .into_iter()
+        .map(|(project, logs)| (project, ActivityStats::from_sessions(&sessionize(logs, idle_gap))))
+        .collect();
+    projects.sort_by(|a, b| b.1.active.cmp(&a.1.active).then_with(|| a.0.cmp(&b.0)));
+    projects
+}
+
+/// Work sessions per local day and project, in date then project order.
+pub fn sessions_by_day_and_project(logs: &[&CommandLog], idle_gap: Duration) -> BTreeMap<(NaiveDate, String), Vec<WorkSession>> {
+    let mut groups: BTreeMap<(NaiveDate, String), Vec<&CommandLog>> = BTreeMap::new();
+    for (project, logs) in group_by_project(logs) {
+        for log in logs {
+            groups.entry((log.started_at.date_naive(), project.clone())).or_default().push(log);
+        }
+    }
+    groups.into_iter().map(|(key, logs)| (key, sessionize(logs, idle_gap))).collect()
+}
+
+fn group_by_project<'a>(logs: &[&'a CommandLog]) -> HashMap<String, Vec<&'a CommandLog>> {
+    let mut roots: HashMap<&str, String> = HashMap::new();
+    let mut projects: HashMap<String, Vec<&CommandLog>> = HashMap::new();
+    for log in logs {
+        let root = roots.entry(&log.cwd).or_insert_with(|| project_root(&log.cwd)).clone();
+        projects.entry(root).or_default().push(log);
+    }
+    projects
+}
+
+/// The closest ancestor of `cwd` that contains a `.git` entry, or `cwd` itself
+/// when there is none (or the directory no longer exists).
+pub fn project_root(cwd: &str) -> String {
+    Path::new(cwd)
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_else(|| cwd.to_string())
+}
+
+/// Formats a duration as e.g. `1h 05m 12s`, `4m 09s` or `37s`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
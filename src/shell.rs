@@ -0,0 +1,29 @@
+/// Shells prynt can generate configuration for.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Quotes `value` as a single shell word. Single quotes work the same way in
+/// bash, zsh and fish as long as embedded quotes are escaped.
+pub fn quote(value: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+/// Whether `name` can be used as an alias name without quoting.
+pub fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c)) && !name.starts_with('-')
+}
+
+/// An alias definition (an abbreviation in fish) expanding `name` to `command`.
+pub fn alias(name: &str, command: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Fish => format!("abbr -a {} {}", name, quote(command, shell)),
+        Shell::Bash | Shell::Zsh => format!("alias {}={}", name, quote(command, shell)),
+    }
+}
@@ -0,0 +1,77 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A named command promoted from history. `{{name}}` placeholders in the
+/// command are filled in when it is run.
+#[derive(Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The TOML layout used by `snippets export` and `snippets import`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SnippetCollection {
+    #[serde(default)]
+    pub snippets: Vec<Snippet>,
+}
+
+pub fn save_snippet(conn: &Connection, snippet: &Snippet, source_id: Option<&str>, overwrite: bool) -> Result<bool> {
+    let verb = if overwrite { "INSERT OR REPLACE" } else { "INSERT OR IGNORE" };
+    let changed = conn.execute(
+        &format!("{} INTO snippets (name, command, description, source_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5)", verb),
+        params![snippet.name, snippet.command, snippet.description, source_id, crate::db::format_ts(&crate::clock::now())],
+    )?;
+    Ok(changed > 0)
+}
+
+pub fn get_snippet(conn: &Connection, name: &str) -> Result<Option<Snippet>> {
+    conn.query_row("SELECT name, command, description FROM snippets WHERE name = ?1", [name], |row| {
+        Ok(Snippet { name: row.get(0)?, command: row.get(1)?, description: row.get(2)? })
+    })
+    .optional()
+}
+
+pub fn list_snippets(conn: &Connection) -> Result<Vec<Snippet>> {
+    let mut stmt = conn.prepare("SELECT name, command, description FROM snippets ORDER BY name")?;
+    stmt.query_map([], |row| Ok(Snippet { name: row.get(0)?, command: row.get(1)?, description: row.get(2)? }))?
+        .collect()
+}
+
+pub fn remove_snippet(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM snippets WHERE name = ?1", [name])? > 0)
+}
+
+/// Placeholder names in order of first appearance, without duplicates.
+pub fn placeholders(command: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = command;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        let name = rest[start + 2..start + 2 + len].trim().to_string();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+/// Replaces every `{{name}}` that has a value. Unknown placeholders are kept.
+pub fn fill(command: &str, values: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = command;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        let raw = &rest[start..start + 2 + len + 2];
+        let name = rest[start + 2..start + 2 + len].trim();
+        out.push_str(&rest[..start]);
+        out.push_str(values.get(name).map(String::as_str).unwrap_or(raw));
+        rest = &rest[start + raw.len()..];
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,198 @@
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use clap::ValueEnum;
+use rusqlite::{params, Connection};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Every plaintext SQLite file starts with this; SQLCipher files look random.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "prynt";
+#[cfg(feature = "keyring")]
+const KEYRING_USER: &str = "database";
+
+/// Where the key of an encrypted database comes from.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Unlock {
+    /// A random key in a file only you can read
+    Keyfile,
+    /// A random key in the OS keyring (Keychain, Credential Manager or Secret Service)
+    Keyring,
+    /// A passphrase, asked for or read from PRYNT_DB_PASSPHRASE
+    Passphrase,
+}
+
+impl std::fmt::Display for Unlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Unlock::Keyfile => "key file",
+            Unlock::Keyring => "OS keyring",
+            Unlock::Passphrase => "passphrase",
+        })
+    }
+}
+
+/// Whether prynt was built with SQLCipher and can encrypt its database.
+pub const SUPPORTED: bool = cfg!(feature = "sqlcipher");
+
+/// Whether the database at `path` is encrypted. A missing or empty file is not.
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// Opens the database at `path`, unlocking it with `key` if it is encrypted.
+/// Keys are what SQLCipher's `PRAGMA key` takes: a passphrase, or a raw key
+/// written `x'<64 hex digits>'`.
+pub fn open(path: &Path, key: Option<&str>) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = key {
+        conn.pragma_update(None, "key", key)?;
+    }
+    Ok(conn)
+}
+
+/// Whether `key` unlocks the database at `path`.
+pub fn check(path: &Path, key: &str) -> bool {
+    open(path, Some(key)).and_then(|conn| conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))).is_ok()
+}
+
+/// Writes a copy of the open database to `dest`, encrypted with `key`, or as
+/// plain SQLite without one.
+pub fn export(conn: &Connection, dest: &Path, key: Option<&str>) -> rusqlite::Result<()> {
+    let _ = std::fs::remove_file(dest);
+    conn.execute("ATTACH DATABASE ?1 AS export KEY ?2", params![dest.to_string_lossy(), key.unwrap_or("")])?;
+    let exported = conn.query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()));
+    conn.execute("DETACH DATABASE export", [])?;
+    exported
+}
+
+/// Copies the open database at `db_path` and the `backups` (all unlocked by
+/// `old_key`) to temporary files encrypted with `new_key`, or unencrypted
+/// without one. Returns each temporary file with the file it replaces, for
+/// `replace` once the new key is stored.
+pub fn stage(conn: &Connection, db_path: &Path, backups: &[PathBuf], old_key: Option<&str>, new_key: Option<&str>) -> rusqlite::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut staged = Vec::new();
+    for path in backups.iter().map(PathBuf::as_path).chain([db_path]) {
+        let staging = PathBuf::from(format!("{}.tmp", path.display()));
+        staged.push((staging.clone(), path.to_path_buf()));
+        let exported = if path == db_path { export(conn, &staging, new_key) } else { open(path, old_key).and_then(|source| export(&source, &staging, new_key)) };
+        if let Err(e) = exported {
+            discard(&staged);
+            return Err(e);
+        }
+    }
+    Ok(staged)
+}
+
+/// Deletes staged files that will not be used.
+pub fn discard(staged: &[(PathBuf, PathBuf)]) {
+    for (staging, _) in staged {
+        let _ = std::fs::remove_file(staging);
+    }
+}
+
+/// Moves staged files over the originals.
+pub fn replace(staged: &[(PathBuf, PathBuf)]) -> std::io::Result<()> {
+    for (staging, path) in staged {
+        make_private(staging)?;
+        std::fs::rename(staging, path)?;
+    }
+    Ok(())
+}
+
+/// A new random raw key.
+pub fn random_key() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("x'{}'", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+pub fn read_keyfile(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|key| key.trim().to_string()).filter(|key| !key.is_empty())
+}
+
+pub fn write_keyfile(path: &Path, key: &str) -> std::io::Result<()> {
+    write_private(path, format!("{}\n", key).as_bytes())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+}
+
+/// The key kept in the OS keyring, if any.
+pub fn read_keyring() -> Option<String> {
+    #[cfg(feature = "keyring")]
+    return keyring_entry().and_then(|entry| entry.get_password()).ok();
+    #[cfg(not(feature = "keyring"))]
+    None
+}
+
+pub fn write_keyring(key: &str) -> Result<(), String> {
+    #[cfg(feature = "keyring")]
+    return keyring_entry().and_then(|entry| entry.set_password(key)).map_err(|e| e.to_string());
+    #[cfg(not(feature = "keyring"))]
+    {
+        let _ = key;
+        Err("this prynt was built without the `keyring` feature".to_string())
+    }
+}
+
+pub fn delete_keyring() {
+    #[cfg(feature = "keyring")]
+    if let Ok(entry) = keyring_entry() {
+        let _ = entry.delete_credential();
+    }
+}
+
+/// Restricts `path` to the current user: `0700` for directories, `0600` for
+/// files. Does nothing on platforms without Unix permissions.
+pub fn make_private(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if path.is_dir() { 0o700 } else { 0o600 };
+        if std::fs::metadata(path)?.permissions().mode() & 0o777 != mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Creates the database at `path` readable only by you if it is missing, and
+/// restricts an existing one and its sidecar files. SQLite gives the
+/// `-journal`, `-wal` and `-shm` files it creates later the database's
+/// permissions, so they are private from the start too.
+pub fn prepare_private(path: &Path) -> std::io::Result<()> {
+    if !path.exists() {
+        write_private(path, b"")?;
+    }
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let file = PathBuf::from(file);
+        if file.exists() {
+            make_private(&file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `contents` to a file that is never readable by other users, not
+/// even for a moment.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    make_private(path)?;
+    file.write_all(contents)
+}
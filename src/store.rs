@@ -0,0 +1,420 @@
+use crate::db::{Annotations, CommandLog};
+use crate::query::{Filter, Order, TimeRange};
+use chrono::{DateTime, Local};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Where command history is kept. `HistoryStore` is the SQLite database the
+/// CLI uses by default; `JsonlStore` and `MemoryStore` only keep commands,
+/// without tags, notes or the daily totals of pruned commands.
+pub trait Store {
+    /// Adds `log` unless a command with its id exists. Returns whether it was added.
+    fn insert(&self, log: &CommandLog) -> Result<bool>;
+
+    /// The commands matching `filter` in `order`, at most `limit` of them.
+    fn logs(&self, filter: &Filter, order: Order, limit: Option<usize>) -> Result<Vec<CommandLog>>;
+
+    /// Deletes the commands with the given ids for good. Returns how many existed.
+    fn delete(&self, ids: &[String]) -> Result<usize>;
+
+    /// Deletes every command.
+    fn clear(&self) -> Result<()>;
+
+    /// Starts a query over all commands, oldest first. Implementations
+    /// return `Query::new(self)`.
+    fn query(&self) -> Query<'_>;
+
+    /// Adds every log, as `insert` does. Returns how many were added.
+    fn insert_all(&self, logs: &[CommandLog]) -> Result<usize> {
+        let mut inserted = 0;
+        for log in logs {
+            inserted += self.insert(log)? as usize;
+        }
+        Ok(inserted)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<CommandLog>> {
+        Ok(self.logs(&Filter::default(), Order::OldestFirst, None)?.into_iter().find(|log| log.id == id))
+    }
+
+    /// Ids starting with `prefix`. At most two are returned, which is enough to
+    /// tell a unique match from an ambiguous one.
+    fn ids_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        let logs = self.logs(&Filter::default(), Order::OldestFirst, None)?;
+        Ok(logs.into_iter().map(|log| log.id).filter(|id| id.starts_with(prefix)).take(2).collect())
+    }
+
+    /// Tags, note and rerun link of a command; empty where they are not kept.
+    fn annotations(&self, _id: &str) -> Result<Annotations> {
+        Ok(Annotations::default())
+    }
+
+    /// Totals of the commands matching `filter`.
+    fn totals(&self, filter: &Filter) -> Result<Totals> {
+        let logs = self.logs(filter, Order::OldestFirst, None)?;
+        let durations = || logs.iter().map(|log| log.duration_ns);
+        Ok(Totals {
+            runs: logs.len() as u64,
+            failures: logs.iter().filter(|log| log.exit_code != 0).count() as u64,
+            duration_ns: durations().sum(),
+            min_duration_ns: durations().min(),
+            max_duration_ns: durations().max(),
+        })
+    }
+
+    /// The `limit` most run commands matching `filter`, counted like `totals`.
+    fn top_commands(&self, filter: &Filter, limit: usize) -> Result<Vec<CommandCount>> {
+        let mut runs: HashMap<String, u64> = HashMap::new();
+        for log in self.logs(filter, Order::OldestFirst, None)? {
+            *runs.entry(log.command).or_insert(0) += 1;
+        }
+        let mut top: Vec<CommandCount> = runs.into_iter().map(|(command, runs)| CommandCount { command, runs }).collect();
+        top.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.command.cmp(&b.command)));
+        top.truncate(limit);
+        Ok(top)
+    }
+
+    /// Totals per working directory, most used first, counted like `totals`.
+    fn folders(&self, filter: &Filter) -> Result<Vec<FolderTotal>> {
+        let mut folders: HashMap<String, FolderTotal> = HashMap::new();
+        for log in self.logs(filter, Order::OldestFirst, None)? {
+            let folder = folders.entry(log.cwd.clone()).or_insert_with(|| FolderTotal { cwd: log.cwd, runs: 0, duration_ns: 0 });
+            folder.runs += 1;
+            folder.duration_ns += log.duration_ns;
+        }
+        let mut folders: Vec<FolderTotal> = folders.into_values().collect();
+        folders.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.cwd.cmp(&b.cwd)));
+        Ok(folders)
+    }
+
+    /// The SQLite database behind this store, for the commands that need
+    /// more than the history itself (tags, snippets, sync, backups).
+    fn sqlite(&self) -> Option<&Connection> {
+        None
+    }
+
+    fn sqlite_mut(&mut self) -> Option<&mut Connection> {
+        None
+    }
+}
+
+/// A query being built by `Store::query`; `fetch` runs it.
+#[must_use]
+pub struct Query<'a> {
+    store: &'a dyn Store,
+    filter: Filter,
+    order: Order,
+    limit: Option<usize>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(store: &'a dyn Store) -> Self {
+        Query { store, filter: Filter::default(), order: Order::default(), limit: None }
+    }
+
+    /// Replaces every criterion set so far with `filter`.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn range(mut self, range: TimeRange) -> Self {
+        self.filter.range = range;
+        self
+    }
+
+    pub fn since(mut self, start: DateTime<Local>) -> Self {
+        self.filter.range.start = Some(start);
+        self
+    }
+
+    pub fn until(mut self, end: DateTime<Local>) -> Self {
+        self.filter.range.end = Some(end);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.filter.tag = Some(tag.into());
+        self
+    }
+
+    pub fn command_contains(mut self, text: impl Into<String>) -> Self {
+        self.filter.command_contains = Some(text.into());
+        self
+    }
+
+    pub fn cwd_contains(mut self, text: impl Into<String>) -> Self {
+        self.filter.cwd_contains = Some(text.into());
+        self
+    }
+
+    pub fn under(mut self, dir: impl Into<String>) -> Self {
+        self.filter.under = Some(dir.into());
+        self
+    }
+
+    pub fn exclude_prynt(mut self) -> Self {
+        self.filter.exclude_prynt = true;
+        self
+    }
+
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn newest_first(self) -> Self {
+        self.order(Order::NewestFirst)
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn fetch(self) -> Result<Vec<CommandLog>> {
+        self.store.logs(&self.filter, self.order, self.limit)
+    }
+
+    /// The first matching command in the query's order.
+    pub fn first(self) -> Result<Option<CommandLog>> {
+        Ok(self.limit(1).fetch()?.pop())
+    }
+
+    pub fn totals(self) -> Result<Totals> {
+        self.store.totals(&self.filter)
+    }
+}
+
+/// Run counts and durations of a set of commands.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Totals {
+    pub runs: u64,
+    pub failures: u64,
+    pub duration_ns: i64,
+    /// `None` when there are no runs
+    pub min_duration_ns: Option<i64>,
+    pub max_duration_ns: Option<i64>,
+}
+
+impl Totals {
+    pub fn average_ns(&self) -> Option<i64> {
+        (self.runs > 0).then(|| self.duration_ns / self.runs as i64)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CommandCount {
+    pub command: String,
+    pub runs: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FolderTotal {
+    pub cwd: String,
+    pub runs: u64,
+    pub duration_ns: i64,
+}
+
+/// `logs` matching `filter`, sorted and limited. Tags are not kept outside
+/// SQLite, so a tag filter matches nothing.
+fn select(logs: impl IntoIterator<Item = CommandLog>, filter: &Filter, order: Order, limit: Option<usize>) -> Vec<CommandLog> {
+    if filter.tag.is_some() {
+        return Vec::new();
+    }
+    let mut selected: Vec<CommandLog> = logs.into_iter().filter(|log| filter.matches(log)).collect();
+    selected.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+    if order == Order::NewestFirst {
+        selected.reverse();
+    }
+    selected.truncate(limit.unwrap_or(usize::MAX));
+    selected
+}
+
+/// History that only lives as long as the value, for tests and tools that
+/// build a history on the fly.
+#[derive(Default)]
+pub struct MemoryStore {
+    logs: RefCell<Vec<CommandLog>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    fn insert(&self, log: &CommandLog) -> Result<bool> {
+        let mut logs = self.logs.borrow_mut();
+        if logs.iter().any(|existing| existing.id == log.id) {
+            return Ok(false);
+        }
+        logs.push(log.clone());
+        Ok(true)
+    }
+
+    fn logs(&self, filter: &Filter, order: Order, limit: Option<usize>) -> Result<Vec<CommandLog>> {
+        Ok(select(self.logs.borrow().iter().cloned(), filter, order, limit))
+    }
+
+    fn delete(&self, ids: &[String]) -> Result<usize> {
+        let mut logs = self.logs.borrow_mut();
+        let before = logs.len();
+        logs.retain(|log| !ids.contains(&log.id));
+        Ok(before - logs.len())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.logs.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+/// One line of a JSONL history file.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Deleted { deleted: String },
+    Command(CommandLog),
+}
+
+/// History in an append-only file with one JSON object per line, for CI
+/// runners and other places without a persistent SQLite database. Deleting
+/// rewrites the file without the deleted commands, and a missing file is an
+/// empty history, so read-only copies work for reports.
+pub struct JsonlStore {
+    path: PathBuf,
+}
+
+impl JsonlStore {
+    /// Uses the file at `path`, which is only created by the first write.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        JsonlStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The commands in the file that were not deleted afterwards.
+    fn read(&self) -> Result<HashMap<String, CommandLog>> {
+        Ok(self.read_lines()?.into_iter().map(|(log, _)| (log.id.clone(), log)).collect())
+    }
+
+    /// Like `read`, with the line each command was read from, in file order.
+    /// Files from older versions may still contain `{"deleted": "<id>"}` lines.
+    fn read_lines(&self) -> Result<Vec<(CommandLog, String)>> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut logs: Vec<(CommandLog, String)> = Vec::new();
+        let mut seen = HashSet::new();
+        for (n, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            match serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", self.path.display(), n + 1, e))? {
+                Line::Deleted { deleted } => {
+                    if seen.remove(&deleted) {
+                        logs.retain(|(log, _)| log.id != deleted);
+                    }
+                }
+                Line::Command(log) => {
+                    if seen.insert(log.id.clone()) {
+                        logs.push((log, line.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Appends `lines` in a single write, so concurrent shell hooks do not interleave.
+    fn append(&self, lines: &[Line]) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let mut out = String::new();
+        for line in lines {
+            out.push_str(&serde_json::to_string(line)?);
+            out.push('\n');
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).append(true);
+        // A new file is readable only by you, like the SQLite database
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&self.path)?;
+        file.write_all(out.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Store for JsonlStore {
+    fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    fn insert(&self, log: &CommandLog) -> Result<bool> {
+        Ok(self.insert_all(std::slice::from_ref(log))? > 0)
+    }
+
+    fn insert_all(&self, logs: &[CommandLog]) -> Result<usize> {
+        let existing = self.read()?;
+        let mut added = HashSet::new();
+        let lines: Vec<Line> = logs
+            .iter()
+            .filter(|log| !existing.contains_key(&log.id) && added.insert(log.id.as_str()))
+            .map(|log| Line::Command(log.clone()))
+            .collect();
+        self.append(&lines)?;
+        Ok(lines.len())
+    }
+
+    fn logs(&self, filter: &Filter, order: Order, limit: Option<usize>) -> Result<Vec<CommandLog>> {
+        Ok(select(self.read()?.into_values(), filter, order, limit))
+    }
+
+    /// Rewrites the file through a temporary file and a rename, so the deleted
+    /// text is gone from it and a failed write leaves the old file intact.
+    fn delete(&self, ids: &[String]) -> Result<usize> {
+        match std::fs::metadata(&self.path) {
+            Ok(meta) if meta.permissions().readonly() => return Err(format!("{} is read-only; nothing was deleted", self.path.display()).into()),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        }
+        let lines = self.read_lines()?;
+        let mut out = String::new();
+        let mut deleted = 0;
+        for (log, line) in &lines {
+            if ids.contains(&log.id) {
+                deleted += 1;
+            } else {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        let staging = PathBuf::from(format!("{}.tmp", self.path.display()));
+        crate::storage::write_private(&staging, out.as_bytes())?;
+        std::fs::rename(&staging, &self.path)?;
+        Ok(deleted)
+    }
+
+    fn clear(&self) -> Result<()> {
+        let ids: Vec<String> = self.read()?.into_keys().collect();
+        self.delete(&ids)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use crate::db::CommandLog;
+use chrono::{DateTime, Duration, Local, Timelike};
+use std::collections::HashMap;
+
+pub struct Suggestion {
+    pub command: String,
+    pub score: f64,
+    pub runs: usize,
+}
+
+/// Weight of a single visit by age: recent use counts far more than old use.
+pub fn frecency_weight(age: Duration) -> f64 {
+    if age < Duration::hours(1) {
+        4.0
+    } else if age < Duration::days(1) {
+        2.0
+    } else if age < Duration::weeks(1) {
+        1.0
+    } else {
+        0.25
+    }
+}
+
+/// Ranks commands from `logs` (everything run in a project) for `cwd`.
+///
+/// Each run adds its frecency weight, doubled when it ran in `cwd` itself and
+/// halved when it failed. The total is boosted by the share of runs that
+/// happened within an hour of the current time of day, and by how often the
+/// command directly followed `last` within a session.
+pub fn rank(logs: &[CommandLog], cwd: &str, now: DateTime<Local>, last: Option<&str>, idle_gap: Duration) -> Vec<Suggestion> {
+    struct Tally {
+        frecency: f64,
+        runs: usize,
+        near_now: usize,
+        after_last: usize,
+    }
+    let mut tallies: HashMap<&str, Tally> = HashMap::new();
+    let mut after_last_total = 0;
+    let mut prev: Option<&CommandLog> = None;
+    for log in logs.iter().filter(|log| !log.is_prynt()) {
+        let tally = tallies.entry(&log.command).or_insert(Tally { frecency: 0.0, runs: 0, near_now: 0, after_last: 0 });
+        let mut weight = frecency_weight(now - log.started_at);
+        if log.cwd == cwd {
+            weight *= 2.0;
+        }
+        if log.exit_code != 0 {
+            weight *= 0.5;
+        }
+        tally.frecency += weight;
+        tally.runs += 1;
+        if hour_distance(log.started_at.hour(), now.hour()) <= 1 {
+            tally.near_now += 1;
+        }
+        if let (Some(prev), Some(last)) = (prev, last)
+            && prev.command == last
+            && log.started_at - prev.ended_at <= idle_gap
+        {
+            tally.after_last += 1;
+            after_last_total += 1;
+        }
+        prev = Some(log);
+    }
+    let mut suggestions: Vec<Suggestion> = tallies
+        .into_iter()
+        .filter(|(command, _)| Some(*command) != last)
+        .map(|(command, tally)| {
+            let time_of_day = tally.near_now as f64 / tally.runs as f64;
+            let successor = if after_last_total > 0 { tally.after_last as f64 / after_last_total as f64 } else { 0.0 };
+            Suggestion {
+                command: command.to_string(),
+                score: tally.frecency * (1.0 + time_of_day) * (1.0 + 2.0 * successor),
+                runs: tally.runs,
+            }
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap().then_with(|| a.command.cmp(&b.command)));
+    suggestions
+}
+
+/// Distance between two hours on a 24-hour clock.
+fn hour_distance(a: u32, b: u32) -> u32 {
+    let d = a.abs_diff(b);
+    d.min(24 - d)
+}
@@ -0,0 +1,261 @@
+use crate::archive::{self, MergeReport, Record};
+use crate::crypto::{self, Keyring};
+use crate::db;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const FORMAT: &str = "prynt-changes";
+
+/// Where change logs are exchanged: a folder on a shared filesystem, or a
+/// `prynt serve` instance that keeps such a folder.
+pub enum Remote {
+    Folder(PathBuf),
+    Server(String),
+}
+
+impl Remote {
+    pub fn parse(target: &str) -> Remote {
+        if target.starts_with("http://") || target.starts_with("https://") {
+            Remote::Server(target.trim_end_matches('/').to_string())
+        } else {
+            Remote::Folder(PathBuf::from(target))
+        }
+    }
+
+    /// The number of lines in each host's change log.
+    fn hosts(&self) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        match self {
+            Remote::Folder(dir) => Ok(folder_hosts(dir)?),
+            Remote::Server(url) => {
+                let body = ureq::get(format!("{}/sync", url)).call()?.body_mut().with_config().limit(u64::MAX).read_to_string()?;
+                Ok(serde_json::from_str(&body)?)
+            }
+        }
+    }
+
+    /// Lines of `host`'s change log starting at line `from`.
+    fn read(&self, host: &str, from: usize) -> Result<Vec<String>, Box<dyn Error>> {
+        match self {
+            Remote::Folder(dir) => Ok(folder_read(dir, host, from)?),
+            Remote::Server(url) => {
+                let body = ureq::get(format!("{}/sync/{}?from={}", url, host, from))
+                    .call()?
+                    .body_mut()
+                    .with_config()
+                    .limit(u64::MAX)
+                    .read_to_string()?;
+                Ok(body.lines().map(String::from).collect())
+            }
+        }
+    }
+
+    /// Appends `lines` to `host`'s change log, which must have `from` lines.
+    fn append(&self, host: &str, from: usize, lines: &[String]) -> Result<(), Box<dyn Error>> {
+        match self {
+            Remote::Folder(dir) => Ok(folder_append(dir, host, from, lines)?),
+            Remote::Server(url) => {
+                let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+                ureq::post(format!("{}/sync/{}?from={}", url, host, from)).send(body)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Host ids are UUIDs; anything else could escape the sync folder.
+pub fn is_host_id(host: &str) -> bool {
+    !host.is_empty() && host.len() <= 64 && host.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+fn log_path(dir: &Path, host: &str) -> PathBuf {
+    dir.join(format!("{}.ndjson", host))
+}
+
+pub fn folder_hosts(dir: &Path) -> std::io::Result<HashMap<String, usize>> {
+    let mut hosts = HashMap::new();
+    if !dir.is_dir() {
+        return Ok(hosts);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some(host) = name.strip_suffix(".ndjson")
+            && is_host_id(host)
+        {
+            hosts.insert(host.to_string(), folder_read(dir, host, 0)?.len());
+        }
+    }
+    Ok(hosts)
+}
+
+/// Complete lines from line `from` on. A last line without its newline is
+/// still being written by another machine and is left for the next sync.
+pub fn folder_read(dir: &Path, host: &str, from: usize) -> std::io::Result<Vec<String>> {
+    let text = match std::fs::read_to_string(log_path(dir, host)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    lines.pop();
+    Ok(lines.into_iter().skip(from).map(String::from).collect())
+}
+
+/// Appends to a change log, refusing if it does not have exactly `from` lines,
+/// which means another machine is using the same host id.
+pub fn folder_append(dir: &Path, host: &str, from: usize, lines: &[String]) -> std::io::Result<()> {
+    let existing = folder_read(dir, host, 0)?.len();
+    if existing != from {
+        return Err(std::io::Error::other(format!("change log {} has {} lines, expected {}", host, existing, from)));
+    }
+    std::fs::create_dir_all(dir)?;
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(log_path(dir, host))?;
+    let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    file.write_all(body.as_bytes())
+}
+
+pub struct SyncReport {
+    pub sent: usize,
+    pub received: usize,
+    /// Other machines with a change log
+    pub machines: usize,
+    pub merge: MergeReport,
+}
+
+/// This machine's id in change logs, created on first sync.
+fn host_id(conn: &Connection) -> rusqlite::Result<String> {
+    if let Some(id) = db::get_meta(conn, "host_id")? {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    db::set_meta(conn, "host_id", &id)?;
+    Ok(id)
+}
+
+/// What identifies a record across machines, and a digest of its contents.
+fn sync_key(record: &Record) -> Option<(&'static str, String, String)> {
+    let (kind, key) = match record {
+        Record::Header { .. } | Record::Rollup { .. } => return None,
+        Record::Command { id, .. } => ("command", id.clone()),
+        Record::Tag { log_id, tag } => ("tag", format!("{} {}", log_id, tag)),
+        Record::Note { log_id, .. } => ("note", log_id.clone()),
+        Record::Snippet { name, .. } => ("snippet", name.clone()),
+        Record::Forget { id } => ("forget", id.clone()),
+    };
+    let json = serde_json::to_string(record).ok()?;
+    let digest = uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, json.as_bytes()).to_string();
+    Some((kind, key, digest))
+}
+
+fn mark_synced(tx: &Connection, target: &str, records: &[Record]) -> rusqlite::Result<()> {
+    for (kind, key, digest) in records.iter().filter_map(sync_key) {
+        tx.execute(
+            "INSERT INTO sync_state (target, kind, key, digest) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (target, kind, key) DO UPDATE SET digest = excluded.digest",
+            params![target, kind, key, digest],
+        )?;
+    }
+    Ok(())
+}
+
+/// Appends everything new or changed here to this machine's change log, then
+/// merges the new entries of every other machine's log. Daily totals stay
+/// local, since other machines already have the raw commands they summarize.
+/// With a `keyring`, every line this machine appends is encrypted, so neither the
+/// shared folder nor the server sees any history.
+pub fn sync(conn: &Connection, target: &str, keyring: Option<&Keyring>) -> Result<SyncReport, Box<dyn Error>> {
+    let remote = Remote::parse(target);
+    let host = host_id(conn)?;
+
+    let mut outgoing = Vec::new();
+    for record in archive::records(conn)? {
+        let Some((kind, key, digest)) = sync_key(&record) else { continue };
+        let synced: Option<String> = conn
+            .query_row("SELECT digest FROM sync_state WHERE target = ?1 AND kind = ?2 AND key = ?3", params![target, kind, key], |row| row.get(0))
+            .optional()?;
+        if synced.as_deref() != Some(digest.as_str()) {
+            outgoing.push(record);
+        }
+    }
+    let hosts = remote.hosts()?;
+
+    let mut incoming = Vec::new();
+    let mut rejected = Vec::new();
+    let mut offsets = Vec::new();
+    for (other, lines) in &hosts {
+        if *other == host {
+            continue;
+        }
+        let offset_key = format!("sync_offset {} {}", target, other);
+        let offset: usize = db::get_meta(conn, &offset_key)?.and_then(|n| n.parse().ok()).unwrap_or(0);
+        if *lines <= offset {
+            continue;
+        }
+        let new_lines = remote.read(other, offset)?;
+        for (n, line) in new_lines.iter().enumerate() {
+            let record: Record = if crypto::is_encrypted(line.as_bytes()) {
+                let Some(keyring) = keyring else {
+                    return Err(format!("change log {} is encrypted; copy the key file from that machine or run `prynt key init --passphrase`", other).into());
+                };
+                serde_json::from_slice(&keyring.decrypt(line)?)?
+            } else if keyring.is_some() {
+                // With a key, only what a machine holding it wrote is trusted; anyone
+                // who can write to the target could otherwise add or forget commands
+                rejected.push(format!("change log {} line {} is not encrypted and was skipped", other, offset + n + 1));
+                continue;
+            } else {
+                serde_json::from_str(line)?
+            };
+            if let Record::Header { format, version, .. } = &record
+                && (format != FORMAT || *version > archive::FORMAT_VERSION)
+            {
+                return Err(format!("change log {} is not a prynt change log this version can read", other).into());
+            }
+            incoming.push(record);
+        }
+        offsets.push((offset_key, offset + new_lines.len()));
+    }
+
+    // Published only once every other log could be read, so a missing key
+    // does not leave half a sync behind
+    let own_lines = hosts.get(&host).copied().unwrap_or(0);
+    if !outgoing.is_empty() {
+        let seal = |json: String| match keyring {
+            Some(keyring) => keyring.encrypt(json.as_bytes()),
+            None => json,
+        };
+        let mut lines = Vec::new();
+        if own_lines == 0 {
+            lines.push(seal(serde_json::to_string(&archive::header(FORMAT))?));
+        }
+        for record in &outgoing {
+            lines.push(seal(serde_json::to_string(record)?));
+        }
+        remote.append(&host, own_lines, &lines)?;
+    }
+
+    // The database is only locked while the results are written, not during
+    // network I/O, so shell hooks logging meanwhile don't time out. Rows
+    // changed since they were read have a new digest and go out next time.
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    mark_synced(&tx, target, &outgoing)?;
+    for (offset_key, offset) in &offsets {
+        db::set_meta(&tx, offset_key, &offset.to_string())?;
+    }
+    let mut merge = archive::apply(&tx, &incoming)?;
+    merge.errors.extend(rejected);
+    // Received rows are already in the change logs and need not be sent back.
+    // Where the local version was kept, it stays the one this machine published.
+    let received: Vec<Record> = incoming
+        .into_iter()
+        .filter(|record| {
+            sync_key(record).is_some_and(|(kind, key, _)| !merge.conflicts.iter().any(|c| c.kind == kind && c.key == key))
+        })
+        .collect();
+    mark_synced(&tx, target, &received)?;
+    tx.commit()?;
+    let machines = hosts.keys().filter(|other| **other != host).count();
+    Ok(SyncReport { sent: outgoing.len(), received: received.len(), machines, merge })
+}
@@ -0,0 +1,209 @@
+use crate::db::{self, CommandLog};
+use crate::session::format_duration;
+use crate::store::Store;
+use chrono::{Duration, Local, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Programs whose time the dashboard counts as building.
+const BUILD_TOOLS: &[&str] = &[
+    "bazel", "bun", "cargo", "clang", "cmake", "deno", "docker", "dotnet", "g++", "gcc", "go", "gradle", "gradlew", "javac", "make", "meson",
+    "mix", "mvn", "ninja", "npm", "pnpm", "rustc", "sbt", "swift", "tsc", "vite", "webpack", "xcodebuild", "yarn",
+];
+
+/// Other well-known programs that may be shared. Anything else could be a
+/// project script or a password typed at the prompt, so it stays private.
+const COMMON_TOOLS: &[&str] = &[
+    "ansible", "apt", "aws", "az", "brew", "cat", "cd", "chmod", "code", "cp", "curl", "docker-compose", "emacs", "find", "gcloud", "gh", "git",
+    "grep", "helm", "htop", "java", "jq", "kubectl", "less", "ls", "man", "mkdir", "mv", "nano", "node", "npx", "nvim", "pip", "pip3", "psql",
+    "pytest", "python", "python3", "rg", "rm", "rsync", "ruby", "scp", "ssh", "tar", "terraform", "tmux", "top", "vim", "wget", "zip",
+];
+
+/// Runs, failures and time of one program on one day; all a teammate shares.
+#[derive(Serialize, Deserialize)]
+pub struct ProgramDay {
+    pub day: NaiveDate,
+    pub program: String,
+    pub runs: u64,
+    pub failures: u64,
+    pub duration_ns: i64,
+}
+
+/// What `prynt share` sends. It replaces everything the member shared for
+/// days from `from` on, so sharing again never counts a command twice.
+#[derive(Serialize, Deserialize)]
+pub struct Upload {
+    /// Random id of this machine for this team, unrelated to its sync host id
+    pub member: String,
+    pub from: NaiveDate,
+    pub stats: Vec<ProgramDay>,
+}
+
+/// The program a command runs, if it can be shared: leading `VAR=value`
+/// assignments and `sudo` are skipped, and only well-known tools are shared,
+/// since any other first word can give away a path, a project or a secret.
+pub fn program(command: &str) -> Option<&str> {
+    let program = command.split_whitespace().find(|w| !((w.contains('=') && !w.starts_with('-')) || *w == "sudo"))?;
+    (BUILD_TOOLS.contains(&program) || COMMON_TOOLS.contains(&program)).then_some(program)
+}
+
+/// Per-day, per-program totals of `logs`. Commands the shell could not find
+/// (exit code 127) are left out, as those are typos or text typed by mistake.
+pub fn aggregate(logs: &[&CommandLog]) -> Vec<ProgramDay> {
+    let mut totals: BTreeMap<(NaiveDate, &str), ProgramDay> = BTreeMap::new();
+    for log in logs.iter().filter(|log| log.exit_code != 127) {
+        let Some(program) = program(&log.command) else { continue };
+        let day = log.started_at.date_naive();
+        let entry = totals.entry((day, program)).or_insert_with(|| ProgramDay { day, program: program.to_string(), runs: 0, failures: 0, duration_ns: 0 });
+        entry.runs += 1;
+        entry.failures += (log.exit_code != 0) as u64;
+        entry.duration_ns += log.duration_ns;
+    }
+    totals.into_values().collect()
+}
+
+/// This machine's id towards the team server at `url`, created on first share.
+pub fn member_id(conn: &Connection, url: &str) -> rusqlite::Result<String> {
+    let key = format!("team_member {}", url);
+    if let Some(id) = db::get_meta(conn, &key)? {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    db::set_meta(conn, &key, &id)?;
+    Ok(id)
+}
+
+/// The first of the last `days` days, or `None` if `days` is negative or
+/// reaches back before the earliest representable date.
+pub fn first_day(days: i64) -> Option<NaiveDate> {
+    let age = Duration::try_days(days).filter(|age| *age >= Duration::zero())?;
+    crate::clock::now().checked_sub_signed(age).map(|start| start.date_naive())
+}
+
+/// What `member` shares: the last `days` days, counted from local midnight
+/// so each day is complete.
+pub fn upload(store: &dyn Store, member: String, days: i64) -> crate::store::Result<Upload> {
+    let from = first_day(days).ok_or_else(|| format!("cannot share the last {} days", days))?;
+    let since = from.and_time(chrono::NaiveTime::MIN).and_local_timezone(Local).earliest().unwrap();
+    let logs = store.query().since(since).exclude_prynt().fetch()?;
+    let counted: Vec<_> = logs.iter().collect();
+    Ok(Upload { member, from, stats: aggregate(&counted) })
+}
+
+pub fn send(url: &str, upload: &Upload) -> Result<(), Box<dyn Error>> {
+    ureq::post(format!("{}/team", url)).header("Content-Type", "application/json").send(serde_json::to_string(upload)?)?;
+    Ok(())
+}
+
+pub fn init_server_db(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS team_stats (
+            member TEXT NOT NULL,
+            day TEXT NOT NULL,
+            program TEXT NOT NULL,
+            runs INTEGER NOT NULL,
+            failures INTEGER NOT NULL,
+            duration_ns INTEGER NOT NULL,
+            PRIMARY KEY (member, day, program)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Checks an upload before it is stored, so the server never keeps anything
+/// a well-behaved client would not send.
+pub fn validate(upload: &Upload) -> Result<(), String> {
+    if !crate::sync::is_host_id(&upload.member) {
+        return Err("invalid member id".to_string());
+    }
+    for stat in &upload.stats {
+        if program(&stat.program) != Some(stat.program.as_str()) {
+            return Err(format!("invalid program name {:?}", stat.program));
+        }
+        if stat.day < upload.from || stat.failures > stat.runs || stat.duration_ns < 0 {
+            return Err(format!("invalid statistics for {} on {}", stat.program, stat.day));
+        }
+    }
+    Ok(())
+}
+
+pub fn store(conn: &mut Connection, upload: &Upload) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM team_stats WHERE member = ?1 AND day >= ?2", params![upload.member, upload.from.to_string()])?;
+    for stat in &upload.stats {
+        tx.execute(
+            "INSERT OR REPLACE INTO team_stats (member, day, program, runs, failures, duration_ns) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![upload.member, stat.day.to_string(), stat.program, stat.runs, stat.failures, stat.duration_ns],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Team-wide totals of one program.
+#[derive(Serialize)]
+pub struct ProgramTotal {
+    pub program: String,
+    pub members: u64,
+    pub runs: u64,
+    pub failures: u64,
+    pub duration_ns: i64,
+}
+
+impl ProgramTotal {
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.failures as f64 / self.runs as f64 }
+    }
+}
+
+/// Totals per program over the last `days` days, most used first.
+pub fn totals(conn: &Connection, days: i64) -> rusqlite::Result<Vec<ProgramTotal>> {
+    let from = first_day(days).unwrap_or(NaiveDate::MIN).to_string();
+    let mut stmt = conn.prepare(
+        "SELECT program, COUNT(DISTINCT member), SUM(runs), SUM(failures), SUM(duration_ns) FROM team_stats
+         WHERE day >= ?1 GROUP BY program ORDER BY SUM(runs) DESC, program",
+    )?;
+    stmt.query_map([from], |row| {
+        Ok(ProgramTotal { program: row.get(0)?, members: row.get(1)?, runs: row.get(2)?, failures: row.get(3)?, duration_ns: row.get(4)? })
+    })?
+    .collect()
+}
+
+/// The plain-text team dashboard for the last `days` days.
+pub fn dashboard(conn: &Connection, days: i64, limit: usize, min_runs: u64) -> rusqlite::Result<String> {
+    let totals = totals(conn, days)?;
+    let from = first_day(days).unwrap_or(NaiveDate::MIN).to_string();
+    let members: u64 = conn.query_row("SELECT COUNT(DISTINCT member) FROM team_stats WHERE day >= ?1", [from], |row| row.get(0))?;
+    let mut out = format!("Team statistics for the last {} days ({} member(s))\n", days, members);
+
+    out.push_str("\nMost time-consuming build commands:\n");
+    let mut builds: Vec<&ProgramTotal> = totals.iter().filter(|t| BUILD_TOOLS.contains(&t.program.as_str())).collect();
+    builds.sort_by_key(|t| std::cmp::Reverse(t.duration_ns));
+    for (i, t) in builds.iter().take(limit).enumerate() {
+        let average = Duration::nanoseconds(t.duration_ns / t.runs.max(1) as i64);
+        out.push_str(&format!(
+            "  {}. {} — {} over {} runs by {} member(s), {} on average\n",
+            i + 1,
+            t.program,
+            format_duration(Duration::nanoseconds(t.duration_ns)),
+            t.runs,
+            t.members,
+            format_duration(average)
+        ));
+    }
+
+    out.push_str(&format!("\nTools that fail most (at least {} runs):\n", min_runs));
+    let mut failing: Vec<&ProgramTotal> = totals.iter().filter(|t| t.runs >= min_runs && t.failures > 0).collect();
+    failing.sort_by(|a, b| b.failure_rate().total_cmp(&a.failure_rate()).then(b.failures.cmp(&a.failures)));
+    for (i, t) in failing.iter().take(limit).enumerate() {
+        out.push_str(&format!("  {}. {} — {:.0}% ({} of {} runs, {} member(s))\n", i + 1, t.program, t.failure_rate() * 100.0, t.failures, t.runs, t.members));
+    }
+
+    out.push_str("\nMost used tools:\n");
+    for (i, t) in totals.iter().take(limit).enumerate() {
+        out.push_str(&format!("  {}. {} — {} runs by {} member(s)\n", i + 1, t.program, t.runs, t.members));
+    }
+    Ok(out)
+}
@@ -0,0 +1,98 @@
+use crate::session::WorkSession;
+use chrono::{Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum TimesheetFormat {
+    Csv,
+    Ics,
+}
+
+/// Rounds `active` up to the next multiple of `increment`. An increment of 0
+/// disables rounding.
+pub fn round_up(active: Duration, increment: Duration) -> Duration {
+    if increment <= Duration::zero() || active <= Duration::zero() {
+        return active;
+    }
+    // Both are at most `Duration::MAX` seconds, so neither step overflows an i64
+    let increment = increment.num_seconds().max(1);
+    let secs = active.num_seconds();
+    Duration::try_seconds((secs + increment - 1) / increment * increment).unwrap_or(Duration::MAX)
+}
+
+/// One CSV row per (day, project) with the raw and rounded active time.
+pub fn render_csv(groups: &BTreeMap<(NaiveDate, String), Vec<WorkSession>>, increment: Duration) -> String {
+    let mut out = String::from("date,project,sessions,active_minutes,rounded_minutes,rounded_hours\n");
+    for ((day, project), sessions) in groups {
+        let active = sessions.iter().map(WorkSession::duration).fold(Duration::zero(), |a, b| a + b);
+        let rounded = round_up(active, increment);
+        out.push_str(&format!(
+            "{},{},{},{:.1},{},{:.2}\n",
+            day,
+            csv_field(project),
+            sessions.len(),
+            active.num_seconds() as f64 / 60.0,
+            rounded.num_minutes(),
+            rounded.num_minutes() as f64 / 60.0,
+        ));
+    }
+    out
+}
+
+/// An iCalendar file with one event per work session.
+pub fn render_ics(groups: &BTreeMap<(NaiveDate, String), Vec<WorkSession>>) -> String {
+    let stamp = ics_time(&crate::clock::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//prynt//timesheet//EN".to_string(),
+    ];
+    for ((_, project), sessions) in groups {
+        let name = Path::new(project).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| project.clone());
+        for session in sessions {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:{}-{}@prynt", session.started_at.timestamp_nanos_opt().unwrap_or_default(), uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, project.as_bytes())));
+            lines.push(format!("DTSTAMP:{}", stamp));
+            lines.push(format!("DTSTART:{}", ics_time(&session.started_at)));
+            lines.push(format!("DTEND:{}", ics_time(&session.ended_at)));
+            lines.push(format!("SUMMARY:{}", ics_text(&name)));
+            lines.push(format!("DESCRIPTION:{}", ics_text(&format!("{} commands in {}", session.commands, project))));
+            lines.push("END:VEVENT".to_string());
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_ics_line(line)).collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn ics_time<Tz: chrono::TimeZone>(ts: &chrono::DateTime<Tz>) -> String {
+    ts.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn ics_text(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// Folds a content line to at most 75 octets per physical line (RFC 5545 §3.1).
+fn fold_ics_line(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+
+/// Parses a relative duration like `90s`, `15m`, `1h`, `30d` or `2w`.
+pub fn parse_duration(spec: &str) -> Option<Duration> {
+    let spec = spec.trim();
+    let unit_at = spec.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = spec.split_at(unit_at);
+    let amount: i64 = amount.parse().ok()?;
+    // None as well when the amount is too large to represent
+    match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" | "min" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+/// Clap value parser for arguments taking a relative duration.
+pub fn duration_arg(spec: &str) -> Result<Duration, String> {
+    parse_duration(spec).ok_or_else(|| format!("invalid duration '{}', expected e.g. 90s, 15m, 1h, 30d or 2w", spec))
+}
+
+/// Parses a point in time: a duration ago (`30d`), a local date (`2024-05-01`,
+/// meaning its midnight) or an RFC 3339 timestamp. A duration reaching back
+/// before the earliest representable time is invalid.
+pub fn parse_time(spec: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let spec = spec.trim();
+    if let Some(age) = parse_duration(spec) {
+        return now.checked_sub_signed(age);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+        return Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest();
+    }
+    DateTime::parse_from_rfc3339(spec).ok().map(|ts| ts.with_timezone(&Local))
+}
+
+/// Clap value parser for arguments taking a point in time.
+pub fn time_arg(spec: &str) -> Result<DateTime<Local>, String> {
+    parse_time(spec, crate::clock::now()).ok_or_else(|| format!("invalid time '{}', expected e.g. 30d, 2024-05-01 or an RFC 3339 timestamp", spec))
+}
@@ -0,0 +1,90 @@
+use crate::db::CommandLog;
+use crate::failures::edit_distance;
+use crate::shell::{self, Shell};
+use chrono::Duration;
+use std::collections::HashMap;
+
+/// Exit status shells use for "command not found".
+const NOT_FOUND: i32 = 127;
+
+/// A mistyped word and what it was corrected to. `position` 0 is the program
+/// itself, 1 its first argument (usually a subcommand), and so on.
+pub struct Typo {
+    pub typo: String,
+    pub fix: String,
+    pub position: usize,
+    pub count: usize,
+}
+
+pub struct TypoReport {
+    pub typos: Vec<Typo>,
+    /// Programs that failed with "command not found" without an obvious fix
+    pub not_found: Vec<(String, usize)>,
+}
+
+/// Pairs every failed command with the next command if that one succeeded
+/// within `retry_window` and differs in exactly one word that is a close edit.
+pub fn analyze(logs: &[&CommandLog], retry_window: Duration) -> TypoReport {
+    let mut typos: HashMap<(String, String, usize), usize> = HashMap::new();
+    let mut not_found: HashMap<String, usize> = HashMap::new();
+    for (i, log) in logs.iter().enumerate() {
+        if log.exit_code == 0 {
+            continue;
+        }
+        let retry = logs
+            .get(i + 1)
+            .filter(|next| next.exit_code == 0 && next.started_at - log.ended_at <= retry_window);
+        match retry.and_then(|next| single_word_fix(&log.command, &next.command)) {
+            Some(key) => *typos.entry(key).or_default() += 1,
+            None if log.exit_code == NOT_FOUND => {
+                if let Some(program) = log.command.split_whitespace().next() {
+                    *not_found.entry(program.to_string()).or_default() += 1;
+                }
+            }
+            None => {}
+        }
+    }
+    let mut typos: Vec<Typo> = typos
+        .into_iter()
+        .map(|((typo, fix, position), count)| Typo { typo, fix, position, count })
+        .collect();
+    typos.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.typo.cmp(&b.typo)));
+    let mut not_found: Vec<_> = not_found.into_iter().collect();
+    not_found.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    TypoReport { typos, not_found }
+}
+
+/// If `failed` and `fixed` have the same words except one, and that word is
+/// within two edits (and less than half its length), returns it with its fix.
+/// Two-letter words only have room for a swap, as in `sl` for `ls`.
+fn single_word_fix(failed: &str, fixed: &str) -> Option<(String, String, usize)> {
+    let a: Vec<&str> = failed.split_whitespace().collect();
+    let b: Vec<&str> = fixed.split_whitespace().collect();
+    if a.len() != b.len() {
+        return None;
+    }
+    let mut diffs = a.iter().zip(&b).enumerate().filter(|(_, (x, y))| x != y);
+    let (position, (typo, fix)) = diffs.next()?;
+    if diffs.next().is_some() {
+        return None;
+    }
+    let distance = edit_distance(typo, fix);
+    let swapped = fix.chars().count() == 2 && typo.chars().rev().eq(fix.chars());
+    ((distance <= 2 && distance * 2 < fix.chars().count()) || swapped).then(|| (typo.to_string(), fix.to_string(), position))
+}
+
+/// Shell aliases mapping each mistyped program name to the right one.
+pub fn render_aliases(typos: &[Typo], shell: Shell, min_count: usize) -> String {
+    let mut out = String::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for typo in typos.iter().filter(|t| t.position == 0 && t.count >= min_count && shell::is_plain_name(&t.typo)) {
+        // Keep only the most common fix when one typo was corrected differently
+        if seen.contains(&typo.typo.as_str()) {
+            continue;
+        }
+        seen.push(&typo.typo);
+        out.push_str(&shell::alias(&typo.typo, &typo.fix, shell));
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,64 @@
+use crate::db::CommandLog;
+use crate::session::split_sessions;
+use chrono::Duration;
+use std::collections::HashMap;
+
+/// A sequence of commands that recurs within work sessions.
+pub struct Workflow {
+    pub commands: Vec<String>,
+    pub count: usize,
+    /// Median time from the start of the first command to the end of the last
+    pub typical_duration: Duration,
+}
+
+/// Finds sequences of 2 to `max_len` consecutive commands that occur at least
+/// `min_count` times within sessions (split at pauses longer than `idle_gap`).
+/// Immediate repeats of a command are collapsed, and a sequence is dropped when
+/// a longer one containing it occurs just as often. Most frequent first, longer
+/// sequences breaking ties.
+pub fn mine(logs: &[&CommandLog], idle_gap: Duration, max_len: usize, min_count: usize) -> Vec<Workflow> {
+    let mut spans: HashMap<Vec<&str>, Vec<Duration>> = HashMap::new();
+    for mut session in split_sessions(logs.iter().copied(), idle_gap) {
+        session.dedup_by(|b, a| a.command.trim() == b.command.trim());
+        for len in 2..=max_len {
+            for window in session.windows(len) {
+                let commands: Vec<&str> = window.iter().map(|log| log.command.trim()).collect();
+                spans.entry(commands).or_default().push(window[len - 1].ended_at - window[0].started_at);
+            }
+        }
+    }
+    let frequent: Vec<(Vec<&str>, Vec<Duration>)> = spans.into_iter().filter(|(_, s)| s.len() >= min_count).collect();
+    let mut workflows: Vec<Workflow> = frequent
+        .iter()
+        .filter(|(commands, durations)| {
+            !frequent.iter().any(|(other, other_durations)| {
+                other.len() > commands.len()
+                    && other_durations.len() >= durations.len()
+                    && other.windows(commands.len()).any(|w| w == commands.as_slice())
+            })
+        })
+        .map(|(commands, durations)| {
+            let mut durations = durations.clone();
+            durations.sort();
+            Workflow {
+                commands: commands.iter().map(|c| c.to_string()).collect(),
+                count: durations.len(),
+                typical_duration: durations[durations.len() / 2],
+            }
+        })
+        .collect();
+    workflows.sort_by(|a, b| {
+        b.count.cmp(&a.count).then_with(|| b.commands.len().cmp(&a.commands.len())).then_with(|| a.commands.cmp(&b.commands))
+    });
+    workflows
+}
+
+/// A POSIX shell script running the workflow's commands, stopping at the first failure.
+pub fn render_script(workflow: &Workflow) -> String {
+    let mut script = String::from("#!/bin/sh\n# Generated by prynt from a recurring command sequence\nset -e\n\n");
+    for command in &workflow.commands {
+        script.push_str(command);
+        script.push('\n');
+    }
+    script
+}
@@ -0,0 +1,194 @@
+//! Fixtures shared by the integration tests: a fixed "now", a factory for
+//! `CommandLog` rows with controlled timestamps, a sample history and golden
+//! file comparison.
+#![allow(dead_code)]
+
+use chrono::{DateTime, Duration, Local};
+use prynt::{db, CommandLog};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+
+/// The time every test runs at, a Wednesday afternoon.
+pub const NOW: &str = "2024-05-15T18:00:00Z";
+
+pub fn now() -> DateTime<Local> {
+    DateTime::parse_from_rfc3339(NOW).unwrap().with_timezone(&Local)
+}
+
+/// A fresh, empty history database, as the CLI would create it.
+pub fn memory_db() -> Connection {
+    db::init_db(Path::new(":memory:"), None).expect("Failed to create in-memory database")
+}
+
+/// Starts building a log of `command`, run one minute before `NOW` in
+/// `/home/dev/app`, taking a second and succeeding.
+pub fn log(command: &str) -> LogBuilder {
+    LogBuilder { command: command.to_string(), started_at: now() - Duration::minutes(1), cwd: "/home/dev/app".to_string(), duration: Duration::seconds(1), exit_code: 0 }
+}
+
+pub struct LogBuilder {
+    command: String,
+    started_at: DateTime<Local>,
+    cwd: String,
+    duration: Duration,
+    exit_code: i32,
+}
+
+impl LogBuilder {
+    /// Started this long before `NOW`.
+    pub fn ago(mut self, ago: Duration) -> Self {
+        self.started_at = now() - ago;
+        self
+    }
+
+    pub fn at(mut self, started_at: DateTime<Local>) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    pub fn cwd(mut self, cwd: &str) -> Self {
+        self.cwd = cwd.to_string();
+        self
+    }
+
+    pub fn took(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn exit(mut self, exit_code: i32) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    /// The log, with an id derived from its start and command so the same
+    /// fixture always gets the same ids.
+    pub fn build(self) -> CommandLog {
+        let seed = format!("{} {}", db::format_ts(&self.started_at), self.command);
+        CommandLog {
+            id: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, seed.as_bytes()).to_string(),
+            started_at: self.started_at,
+            ended_at: self.started_at + self.duration,
+            cwd: self.cwd,
+            command: self.command,
+            exit_code: self.exit_code,
+            duration_ns: self.duration.num_nanoseconds().unwrap(),
+        }
+    }
+}
+
+/// A time on the day of `NOW`.
+fn today(time: &str) -> DateTime<Local> {
+    at_day(0, time)
+}
+
+/// A time `days` days before the day of `NOW`, in UTC like `NOW`.
+fn at_day(days: i64, time: &str) -> DateTime<Local> {
+    let day = DateTime::parse_from_rfc3339(NOW).unwrap().date_naive() - Duration::days(days);
+    DateTime::parse_from_rfc3339(&format!("{}T{}Z", day, time)).unwrap().with_timezone(&Local)
+}
+
+fn secs(secs: f64) -> Duration {
+    Duration::nanoseconds((secs * 1e9) as i64)
+}
+
+/// A week of history for every report to chew on: a few work sessions today
+/// and earlier this week, a typo and its fix, a flaky test, a repeated git
+/// workflow, a long command worth an alias, a `prynt` invocation reports
+/// leave out, and a command older than a week. Directories under `home` exist
+/// so `prynt jump` can list them.
+pub fn sample_history(home: &Path) -> Vec<CommandLog> {
+    let web = "/home/dev/app/web";
+    let infra = "/home/dev/infra";
+    let prynt_dir = home.join("projects/prynt");
+    let website = home.join("projects/website");
+    std::fs::create_dir_all(&prynt_dir).unwrap();
+    std::fs::create_dir_all(&website).unwrap();
+    let (prynt_dir, website) = (prynt_dir.to_str().unwrap(), website.to_str().unwrap());
+    vec![
+        // Three days ago
+        log("git add .").at(at_day(3, "10:00:00")).build(),
+        log("git commit -m 'Initial commit'").at(at_day(3, "10:00:10")).took(secs(0.4)).build(),
+        log("git push origin main").at(at_day(3, "10:00:20")).took(secs(2.5)).build(),
+        log("sl").at(at_day(3, "10:05:00")).took(secs(0.01)).exit(127).build(),
+        log("ls").at(at_day(3, "10:05:05")).took(secs(0.01)).build(),
+        log("gti status").at(at_day(3, "10:06:00")).took(secs(0.01)).exit(127).build(),
+        log("git status").at(at_day(3, "10:06:05")).took(secs(0.1)).build(),
+        log("gti status").at(at_day(3, "10:08:00")).took(secs(0.01)).exit(127).build(),
+        log("git status").at(at_day(3, "10:08:05")).took(secs(0.1)).build(),
+        log("kubectl get pods --namespace staging").cwd(infra).at(at_day(3, "10:30:00")).build(),
+        log("cargo build --release").cwd(prynt_dir).at(at_day(3, "16:00:00")).took(secs(95.0)).build(),
+        log("npm run dev").cwd(website).at(at_day(3, "17:00:00")).took(secs(600.0)).exit(130).build(),
+        // Ten days ago, outside the weekly report
+        log("make release").cwd("/home/dev/old").at(at_day(10, "12:00:00")).took(secs(120.0)).build(),
+        // Today, morning
+        log("git pull").at(today("09:00:00")).took(secs(2.0)).build(),
+        log("cargo buid").at(today("09:00:30")).took(secs(0.05)).exit(101).build(),
+        log("cargo build").at(today("09:00:40")).took(secs(45.0)).build(),
+        log("cargo test").at(today("09:02:00")).took(secs(30.0)).exit(101).build(),
+        log("cargo test").at(today("09:03:00")).took(secs(28.0)).build(),
+        log("git add .").at(today("09:04:00")).took(secs(0.1)).build(),
+        log("git commit -m 'Fix parser'").at(today("09:04:10")).took(secs(0.5)).build(),
+        log("git push origin main").at(today("09:04:20")).took(secs(3.0)).build(),
+        // Today, late morning in a subproject
+        log("npm install").cwd(web).at(today("11:00:00")).took(secs(60.0)).build(),
+        log("npm run build").cwd(web).at(today("11:01:30")).took(secs(20.0)).exit(1).build(),
+        log("npm run build").cwd(web).at(today("11:02:00")).took(secs(22.0)).build(),
+        log("prynt today").cwd(web).at(today("11:03:00")).took(secs(0.1)).build(),
+        // Today, afternoon
+        log("cargo test").at(today("14:00:00")).took(secs(29.0)).exit(101).build(),
+        log("cargo test").at(today("14:01:00")).took(secs(30.0)).build(),
+        log("cargo buid").at(today("14:01:40")).took(secs(0.05)).exit(101).build(),
+        log("cargo build").at(today("14:01:45")).took(secs(12.0)).build(),
+        log("git add .").at(today("14:02:00")).took(secs(0.1)).build(),
+        log("git commit -m 'Add tests'").at(today("14:02:10")).took(secs(0.5)).build(),
+        log("git push origin main").at(today("14:02:20")).took(secs(3.0)).build(),
+        log("docker compose up -d").cwd(infra).at(today("14:05:00")).took(secs(5.0)).build(),
+        log("kubectl get pods --namespace staging").cwd(infra).at(today("14:10:00")).build(),
+        log("kubectl get pods --namespace staging").cwd(infra).at(today("14:11:00")).build(),
+        log("kubectl get pods --namespace staging").cwd(infra).at(today("14:12:00")).build(),
+    ]
+}
+
+/// The log of `command` started at `time` today in `history`.
+pub fn find<'a>(history: &'a [CommandLog], command: &str, time: &str) -> &'a CommandLog {
+    let started_at = today(time);
+    history.iter().find(|log| log.command == command && log.started_at == started_at).expect("no such log in the fixture")
+}
+
+/// Writes `history` to the database the CLI uses under `home`, with a tag,
+/// a note, a snippet and a team member id, and returns the history.
+pub fn seed_home(home: &Path) -> Vec<CommandLog> {
+    let history = sample_history(home);
+    let dir = home.join(".context");
+    std::fs::create_dir_all(&dir).unwrap();
+    let conn = db::init_db(&dir.join("prynt.sqlite"), None).unwrap();
+    for log in &history {
+        db::insert_command_log(&conn, log).unwrap();
+    }
+    let release = find(&history, "git push origin main", "14:02:20");
+    db::add_tags(&conn, &release.id, &["release".to_string()]).unwrap();
+    let build = find(&history, "cargo build", "09:00:40");
+    db::set_note(&conn, &build.id, Some("slow after the dependency bump")).unwrap();
+    let snippet = prynt::snippets::Snippet {
+        name: "restart".to_string(),
+        command: "kubectl rollout restart deployment/{{name}} --namespace {{namespace}}".to_string(),
+        description: Some("Restart a deployment".to_string()),
+    };
+    prynt::snippets::save_snippet(&conn, &snippet, None, false).unwrap();
+    db::set_meta(&conn, "team_member http://team.example", "7d8c5a0e-3f4b-4c2a-9e1d-5b6a7c8d9e0f").unwrap();
+    history
+}
+
+/// Compares `actual` with `tests/golden/<name>`. Run with `UPDATE_GOLDEN=1`
+/// to write the current output instead, then review the diff.
+pub fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("missing {}; run with UPDATE_GOLDEN=1 to create it", path.display()));
+    assert!(expected == actual, "output differs from {}; run with UPDATE_GOLDEN=1 to accept it\n--- expected\n{}\n--- actual\n{}", path.display(), expected, actual);
+}
@@ -0,0 +1,277 @@
+//! Runs every read-only subcommand against the sample history at a fixed
+//! time and compares its output with the files in `tests/golden`.
+
+mod common;
+
+use common::{assert_golden, memory_db, seed_home, NOW};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Runs `prynt args` with `home` as its home directory, in UTC at `NOW`, and
+/// returns what it printed with the home directory replaced by `$HOME`.
+fn prynt(home: &Path, args: &[&str]) -> String {
+    let output = prynt_command(home, args).output().expect("Failed to run prynt");
+    assert!(output.status.success(), "prynt {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().replace(home.to_str().unwrap(), "$HOME")
+}
+
+fn prynt_command(home: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_prynt"));
+    command
+        .args(args)
+        .current_dir(home)
+        .env("HOME", home)
+        .env("TZ", "UTC")
+        .env("PRYNT_NOW", NOW)
+        // Alias names taken by installed programs are skipped, so hide them
+        .env("PATH", home.join("bin"))
+        .env_remove("PRYNT_JSONL")
+        .env_remove("PRYNT_DB_PASSPHRASE")
+        .env_remove("SHELL")
+        .stdin(Stdio::null());
+    command
+}
+
+fn seeded_home() -> tempfile::TempDir {
+    let home = tempfile::tempdir().unwrap();
+    seed_home(home.path());
+    home
+}
+
+/// Each case is a golden file and the arguments whose output it holds.
+fn check(cases: &[(&str, &[&str])]) {
+    let home = seeded_home();
+    for (name, args) in cases {
+        assert_golden(name, &prynt(home.path(), args));
+    }
+}
+
+#[test]
+fn history() {
+    check(&[
+        ("log.txt", &["log"]),
+        ("log-reverse.txt", &["log", "--reverse"]),
+        ("log-tag.txt", &["log", "--tag", "release"]),
+        ("search.txt", &["search", "cargo test"]),
+        ("search-tag.txt", &["search", "git", "--tag", "release"]),
+        ("rerun-dry-run.txt", &["rerun", "push", "--dry-run"]),
+        ("prune-dry-run.txt", &["prune", "--older-than", "7d", "--dry-run"]),
+    ]);
+}
+
+#[test]
+fn today() {
+    check(&[
+        ("today.txt", &["today"]),
+        ("today-export.txt", &["today", "--export"]),
+        ("today-markdown.md", &["today", "--markdown"]),
+        ("today-idle-gap.txt", &["today", "--export", "--idle-gap", "1"]),
+        ("today-tag.txt", &["today", "--export", "--tag", "release"]),
+    ]);
+}
+
+#[test]
+fn weekly() {
+    check(&[
+        ("weekly.txt", &["weekly"]),
+        ("weekly-export.txt", &["weekly", "--export"]),
+        ("weekly-markdown.md", &["weekly", "--markdown"]),
+        ("weekly-idle-gap.txt", &["weekly", "--export", "--idle-gap", "180"]),
+    ]);
+}
+
+#[test]
+fn timesheet() {
+    check(&[
+        ("timesheet.csv", &["timesheet"]),
+        ("timesheet-week.csv", &["timesheet", "--week", "--round", "6"]),
+        // Event ids hash the project path, so this leaves out the projects under $HOME
+        ("timesheet.ics", &["timesheet", "--format", "ics"]),
+    ]);
+}
+
+#[test]
+fn statistics() {
+    check(&[
+        ("summary.txt", &["summary", "app"]),
+        ("top.txt", &["top"]),
+        ("top-3.txt", &["top", "--n", "3"]),
+        ("projects.txt", &["projects"]),
+        ("stats.txt", &["stats"]),
+        ("failures.txt", &["failures"]),
+    ]);
+}
+
+#[test]
+fn suggestions() {
+    check(&[
+        ("suggest.txt", &["suggest", "--cwd", "/home/dev/app"]),
+        ("suggest-last.txt", &["suggest", "--cwd", "/home/dev/app", "--last", "git add ."]),
+        ("suggest-raw.txt", &["suggest", "--cwd", "/home/dev/app", "--prefix", "git", "--raw"]),
+        ("jump.txt", &["jump", "--list"]),
+        ("jump-keyword.txt", &["jump", "--list", "proj", "web"]),
+        ("typos.txt", &["typos"]),
+        ("typos-aliases.sh", &["typos", "--aliases", "bash"]),
+        ("aliases.txt", &["aliases", "suggest", "--min-count", "2"]),
+        ("aliases.fish", &["aliases", "export", "--shell", "fish", "--min-count", "2"]),
+        ("workflows.txt", &["workflows", "--min-count", "2"]),
+    ]);
+}
+
+#[test]
+fn exports() {
+    check(&[
+        ("export.bash", &["export", "--to", "bash"]),
+        ("export.zsh", &["export", "--to", "zsh"]),
+        ("export.fish", &["export", "--to", "fish"]),
+        ("export-project.zsh", &["export", "--to", "zsh", "--project", "/home/dev/app/web"]),
+        ("export-since.bash", &["export", "--to", "bash", "--since", "2024-05-15", "--until", "2024-05-15T12:00:00Z"]),
+    ]);
+}
+
+#[test]
+fn snippets() {
+    check(&[
+        ("snippets.txt", &["snippets", "list"]),
+        ("snippets-show.txt", &["snippets", "show", "restart"]),
+        ("snippets-run-dry-run.txt", &["snippets", "run", "restart", "--set", "name=api", "--set", "namespace=prod", "--dry-run"]),
+        ("snippets.toml", &["snippets", "export"]),
+    ]);
+}
+
+#[test]
+fn share() {
+    check(&[("share-dry-run.json", &["share", "--team-url", "http://team.example", "--dry-run"])]);
+}
+
+/// The hook script for each shell, as `init` prints it when not asked to
+/// append it (stdin is empty). macOS prints a different clock command.
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn init_prints_the_hook_scripts() {
+    let home = tempfile::tempdir().unwrap();
+    for (name, shell) in [("init.bash", "/bin/bash"), ("init.zsh", "/bin/zsh"), ("init.fish", "/usr/bin/fish")] {
+        let output = prynt_command(home.path(), &["init"]).env("SHELL", shell).env_remove("ZSH_VERSION").env_remove("FISH_VERSION").output().unwrap();
+        assert!(output.status.success());
+        assert_golden(name, &String::from_utf8(output.stdout).unwrap().replace(home.path().to_str().unwrap(), "$HOME"));
+    }
+    assert!(!home.path().join(".bashrc").exists(), "init appended without being asked to");
+}
+
+/// Imports each history file in `tests/fixtures` into an empty history and
+/// prints what was imported.
+#[test]
+fn import_reads_shell_history_files() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for (name, from, file) in [("import-bash.txt", "bash", "bash_history"), ("import-zsh.txt", "zsh", "zsh_history"), ("import-fish.txt", "fish", "fish_history")] {
+        let home = tempfile::tempdir().unwrap();
+        let path = home.path().join(file);
+        std::fs::copy(fixtures.join(file), &path).unwrap();
+        let mut output = prynt(home.path(), &["import", "--from", from, "--file", path.to_str().unwrap()]);
+        // Importing again adds nothing
+        output += &prynt(home.path(), &["import", "--from", from, "--file", path.to_str().unwrap()]);
+        output += &prynt(home.path(), &["log"]);
+        assert_golden(name, &output);
+    }
+}
+
+#[test]
+fn tags_and_notes() {
+    let home = tempfile::tempdir().unwrap();
+    let history = seed_home(home.path());
+    let id = &common::find(&history, "cargo test", "09:03:00").id[..8];
+    let mut output = prynt(home.path(), &["tag", id, "ci", "flaky"]);
+    output += &prynt(home.path(), &["note", id, "passed on the second run"]);
+    output += &prynt(home.path(), &["log", "--tag", "flaky"]);
+    output += &prynt(home.path(), &["tag", id, "flaky", "--remove"]);
+    output += &prynt(home.path(), &["note", id, "--clear"]);
+    output += &prynt(home.path(), &["log", "--tag", "ci"]);
+    assert_golden("tags-notes.txt", &output);
+}
+
+#[test]
+fn backups_are_listed() {
+    let home = seeded_home();
+    let mut output = prynt(home.path(), &["backup", "list"]);
+    output += &prynt(home.path(), &["backup", "create"]);
+    output += &prynt(home.path(), &["backup", "list"]);
+    // The size depends on how SQLite lays out the pages
+    let sizes = regex::Regex::new(r"\(\d+ KB\)").unwrap();
+    assert_golden("backup-list.txt", &sizes.replace_all(&output, "(<size>)"));
+}
+
+#[test]
+fn forget_removes_from_every_report() {
+    let home = seeded_home();
+    assert_golden("forget.txt", &prynt(home.path(), &["forget", "--match", "^kubectl", "--yes"]));
+    for args in [&["log"][..], &["today", "--export"], &["top"]] {
+        assert!(!prynt(home.path(), args).contains("kubectl"), "prynt {} still shows a forgotten command", args.join(" "));
+    }
+}
+
+#[test]
+fn logged_commands_show_up() {
+    let home = tempfile::tempdir().unwrap();
+    let started_at_ns = (common::now().timestamp() - 30) * 1_000_000_000;
+    let started_at = started_at_ns.to_string();
+    prynt(home.path(), &["log-cmd", "cargo check", "/home/dev/app", "0", "2.5", "--started-at-ns", &started_at, "--duration-ns", "2500000000"]);
+    prynt(home.path(), &["log-cmd", "prynt forget --match secret", "/home/dev/app", "0", "0.1"]);
+    // The id of a logged command is random
+    let ids = regex::Regex::new("Id: [0-9a-f]{8}").unwrap();
+    assert_golden("log-cmd.txt", &ids.replace_all(&prynt(home.path(), &["log"]), "Id: <id>"));
+}
+
+/// The JSONL backend must print the same reports as SQLite for the same
+/// history; tags, notes and snippets only exist in SQLite, so they are left out.
+#[test]
+fn jsonl_backend_matches_sqlite() {
+    let home = seeded_home();
+    let history = common::sample_history(home.path());
+    let file = home.path().join("history.jsonl");
+    let store = prynt::JsonlStore::open(&file);
+    prynt::Store::insert_all(&store, &history).unwrap();
+    for args in [
+        &["today", "--export"][..],
+        &["weekly", "--markdown"],
+        &["timesheet", "--week"],
+        &["top"],
+        &["projects"],
+        &["stats"],
+        &["failures"],
+        &["typos"],
+        &["workflows", "--min-count", "2"],
+        &["export", "--to", "zsh"],
+    ] {
+        let sqlite = prynt(home.path(), args);
+        let output = prynt_command(home.path(), args).env("PRYNT_JSONL", &file).output().unwrap();
+        assert!(output.status.success());
+        let jsonl = String::from_utf8(output.stdout).unwrap().replace(home.path().to_str().unwrap(), "$HOME");
+        assert_eq!(sqlite, jsonl, "prynt {} differs between backends", args.join(" "));
+    }
+}
+
+#[test]
+fn team_dashboard() {
+    let mut conn = memory_db();
+    prynt::team::init_server_db(&conn).unwrap();
+    let _clock = prynt::clock::freeze(common::now());
+    let day = common::now().date_naive();
+    let stat = |program: &str, runs, failures, secs: i64| prynt::team::ProgramDay { day, program: program.to_string(), runs, failures, duration_ns: secs * 1_000_000_000 };
+    let uploads = [
+        ("1b4e28ba-2fa1-11d2-883f-0016d3cca427", vec![stat("cargo", 40, 6, 1800), stat("git", 120, 2, 60), stat("npm", 10, 4, 600)]),
+        ("6fa459ea-ee8a-3ca4-894e-db77e160355e", vec![stat("cargo", 25, 1, 900), stat("git", 80, 0, 40), stat("kubectl", 12, 3, 24)]),
+    ];
+    for (member, stats) in uploads {
+        let upload = prynt::team::Upload { member: member.to_string(), from: day - chrono::Duration::days(29), stats };
+        prynt::team::validate(&upload).unwrap();
+        prynt::team::store(&mut conn, &upload).unwrap();
+    }
+    // Anything but a well-known tool could be a project name or a password
+    for program in ["deploy-acme-prod", "hunter2"] {
+        let upload = prynt::team::Upload { member: "1b4e28ba-2fa1-11d2-883f-0016d3cca427".to_string(), from: day, stats: vec![stat(program, 1, 1, 0)] };
+        assert!(prynt::team::validate(&upload).is_err(), "the server accepted {}", program);
+    }
+    assert_golden("team-dashboard.txt", &prynt::team::dashboard(&conn, 30, 10, 5).unwrap());
+    let totals = prynt::team::totals(&conn, 30).unwrap();
+    assert_golden("team-totals.json", &serde_json::to_string_pretty(&totals).unwrap());
+}
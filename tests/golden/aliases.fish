@@ -0,0 +1,5 @@
+abbr -a kgpns 'kubectl get pods --namespace staging'
+abbr -a kgpn 'kubectl get pods --namespace'
+abbr -a gpom 'git push origin main'
+abbr -a gpo 'git push origin'
+abbr -a gcm 'git commit -m'
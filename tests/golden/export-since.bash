@@ -0,0 +1,24 @@
+#1715763600
+git pull
+#1715763630
+cargo buid
+#1715763640
+cargo build
+#1715763720
+cargo test
+#1715763780
+cargo test
+#1715763840
+git add .
+#1715763850
+git commit -m 'Fix parser'
+#1715763860
+git push origin main
+#1715770800
+npm install
+#1715770890
+npm run build
+#1715770920
+npm run build
+#1715770980
+prynt today
@@ -0,0 +1,72 @@
+#1714910400
+make release
+#1715508000
+git add .
+#1715508010
+git commit -m 'Initial commit'
+#1715508020
+git push origin main
+#1715508300
+sl
+#1715508305
+ls
+#1715508360
+gti status
+#1715508365
+git status
+#1715508480
+gti status
+#1715508485
+git status
+#1715509800
+kubectl get pods --namespace staging
+#1715529600
+cargo build --release
+#1715533200
+npm run dev
+#1715763600
+git pull
+#1715763630
+cargo buid
+#1715763640
+cargo build
+#1715763720
+cargo test
+#1715763780
+cargo test
+#1715763840
+git add .
+#1715763850
+git commit -m 'Fix parser'
+#1715763860
+git push origin main
+#1715770800
+npm install
+#1715770890
+npm run build
+#1715770920
+npm run build
+#1715770980
+prynt today
+#1715781600
+cargo test
+#1715781660
+cargo test
+#1715781700
+cargo buid
+#1715781705
+cargo build
+#1715781720
+git add .
+#1715781730
+git commit -m 'Add tests'
+#1715781740
+git push origin main
+#1715781900
+docker compose up -d
+#1715782200
+kubectl get pods --namespace staging
+#1715782260
+kubectl get pods --namespace staging
+#1715782320
+kubectl get pods --namespace staging
@@ -0,0 +1,72 @@
+- cmd: make release
+  when: 1714910400
+- cmd: git add .
+  when: 1715508000
+- cmd: git commit -m 'Initial commit'
+  when: 1715508010
+- cmd: git push origin main
+  when: 1715508020
+- cmd: sl
+  when: 1715508300
+- cmd: ls
+  when: 1715508305
+- cmd: gti status
+  when: 1715508360
+- cmd: git status
+  when: 1715508365
+- cmd: gti status
+  when: 1715508480
+- cmd: git status
+  when: 1715508485
+- cmd: kubectl get pods --namespace staging
+  when: 1715509800
+- cmd: cargo build --release
+  when: 1715529600
+- cmd: npm run dev
+  when: 1715533200
+- cmd: git pull
+  when: 1715763600
+- cmd: cargo buid
+  when: 1715763630
+- cmd: cargo build
+  when: 1715763640
+- cmd: cargo test
+  when: 1715763720
+- cmd: cargo test
+  when: 1715763780
+- cmd: git add .
+  when: 1715763840
+- cmd: git commit -m 'Fix parser'
+  when: 1715763850
+- cmd: git push origin main
+  when: 1715763860
+- cmd: npm install
+  when: 1715770800
+- cmd: npm run build
+  when: 1715770890
+- cmd: npm run build
+  when: 1715770920
+- cmd: prynt today
+  when: 1715770980
+- cmd: cargo test
+  when: 1715781600
+- cmd: cargo test
+  when: 1715781660
+- cmd: cargo buid
+  when: 1715781700
+- cmd: cargo build
+  when: 1715781705
+- cmd: git add .
+  when: 1715781720
+- cmd: git commit -m 'Add tests'
+  when: 1715781730
+- cmd: git push origin main
+  when: 1715781740
+- cmd: docker compose up -d
+  when: 1715781900
+- cmd: kubectl get pods --namespace staging
+  when: 1715782200
+- cmd: kubectl get pods --namespace staging
+  when: 1715782260
+- cmd: kubectl get pods --namespace staging
+  when: 1715782320
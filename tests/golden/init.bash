@@ -0,0 +1,29 @@
+# The following snippet will enable prynt logging for your shell:
+
+[[ -f ~/.bash-preexec.sh ]] && source ~/.bash-preexec.sh
+
+function prynt_preexec() {
+    export PRYNT_CMD_START_TIME=$(date +%s%N)
+    export PRYNT_CMD_TO_LOG="$1"
+}
+function prynt_precmd() {
+    local exit_code=$?
+    if [ -n "$PRYNT_CMD_START_TIME" ] && [ -n "$PRYNT_CMD_TO_LOG" ]; then
+        local end_time=$(date +%s%N)
+        local duration_ns=$((end_time - PRYNT_CMD_START_TIME))
+        local duration_s=$(awk "BEGIN {print $duration_ns/1000000000}")
+        if [[ ! "$PRYNT_CMD_TO_LOG" =~ ^prynt($|[[:space:]]) ]]; then
+            prynt log-cmd "$PRYNT_CMD_TO_LOG" "$PWD" "$exit_code" "$duration_s" --started-at-ns "$PRYNT_CMD_START_TIME" --duration-ns "$duration_ns"
+        fi
+        unset PRYNT_CMD_START_TIME
+        unset PRYNT_CMD_TO_LOG
+    fi
+}
+preexec_functions+=(prynt_preexec)
+precmd_functions+=(prynt_precmd)
+
+j() { local dir; dir="$(prynt jump -- "$@")" && cd "$dir"; }
+ji() { local dir; dir="$(prynt jump --interactive -- "$@")" && cd "$dir"; }
+
+
+Would you like to append this to $HOME/.bashrc? [y/N]: Not appended. You can manually add the snippet above to your shell config file.
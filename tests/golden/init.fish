@@ -0,0 +1,30 @@
+# The following snippet will enable prynt logging for your shell:
+
+function prynt_preexec --on-event fish_preexec
+    set -g PRYNT_CMD_START_TIME (date +%s%N )
+    set -g PRYNT_CMD_TO_LOG $argv[1]
+end
+
+function prynt_precmd --on-event fish_prompt
+    set -l exit_code $status
+    if test -n "$PRYNT_CMD_START_TIME" -a -n "$PRYNT_CMD_TO_LOG"
+        set end_time (date +%s%N )
+        set duration_ns (math $end_time - $PRYNT_CMD_START_TIME)
+        set duration_s (math --scale 2 $duration_ns / 1000000000)
+        if not string match -r '^prynt($|\s)' -- $PRYNT_CMD_TO_LOG
+            prynt log-cmd "$PRYNT_CMD_TO_LOG" "$PWD" "$exit_code" "$duration_s" --started-at-ns "$PRYNT_CMD_START_TIME" --duration-ns "$duration_ns"
+        end
+        set -e PRYNT_CMD_START_TIME
+        set -e PRYNT_CMD_TO_LOG
+    end
+end
+
+function j
+    set -l dir (prynt jump -- $argv); and cd $dir
+end
+function ji
+    set -l dir (prynt jump --interactive -- $argv); and cd $dir
+end
+
+
+Would you like to append this to $HOME/.config/fish/config.fish? [y/N]: Not appended. You can manually add the snippet above to your shell config file.
@@ -0,0 +1 @@
+alias gti='git'
@@ -0,0 +1,151 @@
+//! Property tests for time ranges, the store backends and the period reports.
+
+mod common;
+
+use chrono::{DateTime, Duration, Local};
+use common::{log, now};
+use prynt::report::PeriodReport;
+use prynt::session::{self, ActivityStats};
+use prynt::{clock, CommandLog, Filter, HistoryStore, JsonlStore, MemoryStore, Order, Store, TimeRange};
+use proptest::prelude::*;
+
+/// Commands that exercise substring matching (`%` and `_` are LIKE
+/// wildcards in SQLite) and the `prynt` exclusion.
+const COMMANDS: &[&str] = &["git status", "Git Status", "cargo test", "cargo test_all", "echo 100%", "echo é", "prynt", "prynt today", "  prynt log", "prynty", "xprynt today"];
+const CWDS: &[&str] = &["/a", "/a/b", "/a/b/c", "/ab", "/a_b", "/a%b", "/A", "/é"];
+const PARTS: &[&str] = &["", "git", "GIT", "test_", "%", "_", "é", "prynt", "/a", "/a/", "b"];
+
+/// A time within a couple of days of `NOW`, to the nanosecond.
+fn time() -> impl Strategy<Value = DateTime<Local>> {
+    (-2 * 86_400_000_000_000i64..86_400_000_000_000).prop_map(|ns| now() + Duration::nanoseconds(ns))
+}
+
+fn command_log() -> impl Strategy<Value = CommandLog> {
+    (0..COMMANDS.len(), 0..CWDS.len(), time(), 0i64..600_000_000_000, prop_oneof![Just(0), Just(1), Just(127)])
+        .prop_map(|(command, cwd, started_at, duration_ns, exit_code)| {
+            log(COMMANDS[command]).cwd(CWDS[cwd]).at(started_at).took(Duration::nanoseconds(duration_ns)).exit(exit_code).build()
+        })
+}
+
+/// Logs with distinct ids, as the stores require.
+fn history() -> impl Strategy<Value = Vec<CommandLog>> {
+    prop::collection::vec(command_log(), 0..40).prop_map(|mut logs| {
+        logs.sort_by(|a, b| a.id.cmp(&b.id));
+        logs.dedup_by(|a, b| a.id == b.id);
+        logs
+    })
+}
+
+fn range() -> impl Strategy<Value = TimeRange> {
+    (prop::option::of(time()), prop::option::of(time())).prop_map(|(start, end)| TimeRange { start, end })
+}
+
+fn part() -> impl Strategy<Value = Option<String>> {
+    prop::option::of((0..PARTS.len()).prop_map(|i| PARTS[i].to_string()))
+}
+
+fn filter() -> impl Strategy<Value = Filter> {
+    (range(), part(), part(), prop::option::of((0..CWDS.len()).prop_map(|i| CWDS[i].to_string())), any::<bool>()).prop_map(
+        |(range, command_contains, cwd_contains, under, exclude_prynt)| Filter { range, tag: None, command_contains, cwd_contains, under, exclude_prynt },
+    )
+}
+
+proptest! {
+    #[test]
+    fn range_contains_its_start_but_not_its_end(start in time(), end in time(), time in time()) {
+        let range = TimeRange::between(start, end);
+        prop_assert_eq!(range.contains(time), start <= time && time < end);
+        prop_assert_eq!(TimeRange::since(start).contains(time), start <= time);
+        prop_assert_eq!(TimeRange::until(end).contains(time), time < end);
+        prop_assert!(TimeRange::all().contains(time));
+    }
+
+    #[test]
+    fn last_day_is_the_24_hours_up_to_now(at in time(), ago_ns in -3_600_000_000_000i64..2 * 86_400_000_000_000) {
+        let _clock = clock::freeze(at);
+        let started_at = at - Duration::nanoseconds(ago_ns);
+        let today = TimeRange::last(Duration::hours(24), clock::now());
+        // Commands still running, or logged with a clock slightly ahead, count as today
+        prop_assert_eq!(today.contains(started_at), ago_ns <= 86_400_000_000_000);
+    }
+
+    #[test]
+    fn backends_agree(logs in history(), filter in filter(), newest_first in any::<bool>(), limit in prop::option::of(0usize..10)) {
+        let sqlite = HistoryStore::open_in_memory().unwrap();
+        let memory = MemoryStore::new();
+        let dir = tempfile::tempdir().unwrap();
+        let jsonl = JsonlStore::open(dir.path().join("history.jsonl"));
+        let order = if newest_first { Order::NewestFirst } else { Order::OldestFirst };
+        let stores: [&dyn Store; 3] = [&sqlite, &memory, &jsonl];
+        for store in stores {
+            prop_assert_eq!(store.insert_all(&logs).unwrap(), logs.len());
+        }
+
+        let expected: Vec<&CommandLog> = {
+            let mut matching: Vec<&CommandLog> = logs.iter().filter(|log| filter.matches(log)).collect();
+            matching.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+            if newest_first {
+                matching.reverse();
+            }
+            matching.truncate(limit.unwrap_or(usize::MAX));
+            matching
+        };
+        let reference = memory.totals(&filter).unwrap();
+        prop_assert_eq!(reference.runs, logs.iter().filter(|log| filter.matches(log)).count() as u64);
+        for store in stores {
+            let found = store.logs(&filter, order, limit).unwrap();
+            prop_assert_eq!(found.iter().collect::<Vec<_>>(), expected.clone());
+            prop_assert_eq!(store.totals(&filter).unwrap(), reference.clone());
+            prop_assert_eq!(store.top_commands(&filter, 5).unwrap(), memory.top_commands(&filter, 5).unwrap());
+            prop_assert_eq!(store.folders(&filter).unwrap(), memory.folders(&filter).unwrap());
+        }
+    }
+
+    #[test]
+    fn sessions_cover_every_command_once(logs in history(), gap_minutes in 0i64..120) {
+        let idle_gap = Duration::minutes(gap_minutes);
+        let sessions = session::split_sessions(&logs, idle_gap);
+        prop_assert_eq!(sessions.iter().map(Vec::len).sum::<usize>(), logs.len());
+        for pair in sessions.windows(2) {
+            let end = pair[0].iter().map(|log| log.ended_at).max().unwrap();
+            prop_assert!(pair[1][0].started_at - end > idle_gap);
+        }
+        let stats = ActivityStats::from_sessions(&session::sessionize(&logs, idle_gap));
+        prop_assert!(stats.idle >= Duration::zero());
+        prop_assert!(stats.longest_streak <= stats.active);
+        prop_assert_eq!(stats.sessions, sessions.len());
+    }
+
+    #[test]
+    fn report_adds_up(logs in history(), gap_minutes in 1i64..120) {
+        let report = PeriodReport::build("Today", &logs, Duration::minutes(gap_minutes));
+        let counted: Vec<&CommandLog> = logs.iter().filter(|log| !log.is_prynt()).collect();
+        prop_assert_eq!(report.total_commands, counted.len());
+        let total_secs: f64 = counted.iter().map(|log| log.duration_secs()).sum();
+        prop_assert!((report.total_secs - total_secs).abs() < 1e-6);
+        prop_assert!(!report.render_text().contains("-0.00"));
+
+        let span = counted.iter().map(|log| log.ended_at).max().zip(counted.iter().map(|log| log.started_at).min()).map(|(end, start)| end - start);
+        prop_assert_eq!(report.uptime, span);
+        prop_assert_eq!(report.activity.active + report.activity.idle, span.unwrap_or_default());
+        let day_sessions: usize = report.by_day.values().map(|day| day.sessions).sum();
+        prop_assert!(day_sessions >= report.activity.sessions);
+
+        prop_assert!(report.top_commands.len() <= 3);
+        prop_assert!(report.top_commands.windows(2).all(|pair| pair[0].1 > pair[1].1 || (pair[0].1 == pair[1].1 && pair[0].0 < pair[1].0)));
+        for (command, count) in &report.top_commands {
+            prop_assert_eq!(*count, counted.iter().filter(|log| &log.command == command).count());
+        }
+        prop_assert!(report.top_folders.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    #[test]
+    fn report_ignores_input_order(logs in history(), seed in any::<u64>()) {
+        let mut shuffled = logs.clone();
+        // A cheap deterministic shuffle; proptest shrinks the seed
+        shuffled.sort_by_key(|log| log.id.bytes().fold(seed, |hash, byte| hash.rotate_left(5) ^ byte as u64));
+        let idle_gap = Duration::minutes(15);
+        prop_assert_eq!(PeriodReport::build("Weekly", &logs, idle_gap).render_text(), PeriodReport::build("Weekly", &shuffled, idle_gap).render_text());
+        prop_assert_eq!(PeriodReport::build("Weekly", &logs, idle_gap).render_markdown(), PeriodReport::build("Weekly", &shuffled, idle_gap).render_markdown());
+    }
+}